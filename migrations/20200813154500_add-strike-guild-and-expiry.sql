@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE strikes ADD COLUMN guild_id TEXT;
+ALTER TABLE strikes ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE strikes ADD COLUMN expires_at TIMESTAMPTZ;
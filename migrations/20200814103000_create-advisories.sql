@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE advisories (
+  id SERIAL PRIMARY KEY,
+  userid TEXT NOT NULL,
+  guild_id TEXT NOT NULL,
+  moderator TEXT NOT NULL,
+  reason TEXT NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  is_withdrawn BOOLEAN NOT NULL DEFAULT 'f'
+);
@@ -0,0 +1,18 @@
+-- Add migration script here
+CREATE TABLE raids (
+  guild_id TEXT PRIMARY KEY,
+  moderator TEXT NOT NULL,
+  lockdown BOOLEAN NOT NULL DEFAULT 'f',
+  join_action TEXT NOT NULL DEFAULT 'none',
+  started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  ends_at TIMESTAMPTZ
+);
+
+CREATE TABLE raid_channels (
+  guild_id TEXT NOT NULL REFERENCES raids(guild_id) ON DELETE CASCADE,
+  channel_id TEXT NOT NULL,
+  slow_mode_rate INTEGER NOT NULL DEFAULT 0,
+  everyone_allow BIGINT,
+  everyone_deny BIGINT,
+  PRIMARY KEY (guild_id, channel_id)
+);
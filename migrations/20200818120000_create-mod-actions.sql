@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE mod_actions (
+  id SERIAL PRIMARY KEY,
+  guild_id TEXT NOT NULL,
+  target TEXT,
+  moderator TEXT NOT NULL,
+  action_type TEXT NOT NULL,
+  reason TEXT,
+  details TEXT,
+  case_id INTEGER,
+  old_value TEXT,
+  new_value TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX mod_actions_guild_id_idx ON mod_actions (guild_id, created_at);
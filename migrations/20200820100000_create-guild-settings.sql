@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE guild_settings (
+  guild_id TEXT NOT NULL,
+  name TEXT NOT NULL,
+  value TEXT NOT NULL,
+  PRIMARY KEY (guild_id, name)
+);
@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE verified_roles (
+  userid TEXT NOT NULL,
+  award TEXT NOT NULL,
+  verified_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (userid, award)
+);
+
+CREATE TABLE age_groups (
+  userid TEXT PRIMARY KEY,
+  age_group TEXT NOT NULL
+);
@@ -0,0 +1,23 @@
+-- Add migration script here
+-- Entries with no guild_id are on the global list
+CREATE TABLE banned_words (
+  id SERIAL PRIMARY KEY,
+  guild_id TEXT,
+  word TEXT NOT NULL,
+  mode TEXT NOT NULL DEFAULT 'word'
+);
+
+CREATE UNIQUE INDEX banned_words_entry_idx ON banned_words ((COALESCE(guild_id, '')), word);
+
+CREATE TABLE allowed_words (
+  guild_id TEXT NOT NULL,
+  word TEXT NOT NULL,
+  PRIMARY KEY (guild_id, word)
+);
+
+CREATE TABLE wordfilter_exemptions (
+  guild_id TEXT NOT NULL,
+  target_id TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  PRIMARY KEY (guild_id, target_id)
+);
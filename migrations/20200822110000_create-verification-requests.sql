@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TABLE verification_requests (
+  id SERIAL PRIMARY KEY,
+  requester TEXT NOT NULL,
+  -- One attachment URL per line
+  attachments TEXT NOT NULL,
+  requested_award TEXT,
+  status TEXT NOT NULL DEFAULT 'pending',
+  reviewer TEXT,
+  decided_at TIMESTAMPTZ,
+  deny_reason TEXT,
+  queue_message TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX verification_requests_status_idx ON verification_requests (status, created_at);
+CREATE UNIQUE INDEX verification_requests_queue_message_idx ON verification_requests (queue_message);
@@ -0,0 +1,14 @@
+-- Add migration script here
+ALTER TABLE verification_requests ADD COLUMN resubmissions INTEGER NOT NULL DEFAULT 0;
+
+-- Every decision on a request, so earlier denials survive a resubmission
+CREATE TABLE verification_decisions (
+  id SERIAL PRIMARY KEY,
+  request_id INTEGER NOT NULL REFERENCES verification_requests (id),
+  reviewer TEXT NOT NULL,
+  status TEXT NOT NULL,
+  reason TEXT,
+  decided_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX verification_decisions_request_id_idx ON verification_decisions (request_id);
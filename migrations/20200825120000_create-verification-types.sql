@@ -0,0 +1,23 @@
+-- Add migration script here
+CREATE TABLE verification_types (
+  id SERIAL PRIMARY KEY,
+  -- Stored in verified_roles.award and used in the verified_role_<key> server settings
+  key TEXT NOT NULL UNIQUE,
+  name TEXT NOT NULL,
+  emoji TEXT NOT NULL,
+  proof TEXT NOT NULL,
+  retired BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX verification_types_emoji_idx ON verification_types (emoji) WHERE NOT retired;
+
+INSERT INTO verification_types (key, name, emoji, proof) VALUES
+  ('eagle', 'Eagle Scout', '🦅', 'Patch, Card, or Certificate'),
+  ('summit', 'Summit/Silver', '⛰', 'Patch, Card, or Certificate'),
+  ('campstaff', 'Camp Staff', '🏕', 'Name tag or shirt'),
+  ('ypt', 'YPT', '🛂', 'Certificate. PDF is acceptable for this verification'),
+  ('ordeal', 'Ordeal', '↗', 'Sash or membership card'),
+  ('brotherhood', 'Brotherhood', '🟥', 'Sash or membership card'),
+  ('vigil', 'Vigil', '🔺', 'Sash or membership card'),
+  ('quartermaster', 'Quartermaster', '🚢', 'Medal, Patch, Card, and Certificate');
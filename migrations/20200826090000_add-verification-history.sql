@@ -0,0 +1,22 @@
+-- Add migration script here
+-- How long a verification lasts before it has to be renewed, or NULL if it never expires
+ALTER TABLE verification_types ADD COLUMN valid_days INTEGER;
+UPDATE verification_types SET valid_days = 730 WHERE key = 'ypt';
+
+-- Revoked and expired verifications are kept, so a user can have several rows for one award
+ALTER TABLE verified_roles DROP CONSTRAINT verified_roles_pkey;
+ALTER TABLE verified_roles
+  ADD COLUMN id SERIAL PRIMARY KEY,
+  -- NULL for verifications imported from the old databases
+  ADD COLUMN approver TEXT,
+  ADD COLUMN expires_at TIMESTAMPTZ,
+  ADD COLUMN expiry_notified BOOLEAN NOT NULL DEFAULT FALSE,
+  ADD COLUMN revoked_at TIMESTAMPTZ,
+  ADD COLUMN revoked_by TEXT,
+  ADD COLUMN revoke_reason TEXT;
+
+-- Existing YPT verifications have no real completion date, so they get a full term from now
+UPDATE verified_roles SET expires_at = NOW() + INTERVAL '730 days' WHERE award = 'ypt' AND expires_at IS NULL;
+
+CREATE UNIQUE INDEX verified_roles_active_idx ON verified_roles (userid, award) WHERE revoked_at IS NULL;
+CREATE INDEX verified_roles_expires_at_idx ON verified_roles (expires_at) WHERE revoked_at IS NULL;
@@ -0,0 +1,5 @@
+-- Add migration script here
+-- Strikes from before guild scoping weren't tied to a server. They're assigned to the DSC
+-- hub server, where the network's moderators can still review and manage them.
+UPDATE strikes SET guild_id = '646540745443901469' WHERE guild_id IS NULL;
+ALTER TABLE strikes ALTER COLUMN guild_id SET NOT NULL;
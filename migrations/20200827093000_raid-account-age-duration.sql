@@ -0,0 +1,3 @@
+-- Add migration script here
+-- raid_detect_account_age is now a duration stored in seconds, like the other time settings
+UPDATE guild_settings SET value = (value::BIGINT * 86400)::TEXT WHERE name = 'raid_detect_account_age' AND value ~ '^[0-9]+$';
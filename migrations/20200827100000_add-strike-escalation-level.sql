@@ -0,0 +1,3 @@
+-- Add migration script here
+-- The harshest escalation each strike has led to: 0 none, 1 timeout, 2 kick, 3 ban
+ALTER TABLE strikes ADD COLUMN escalation_level INTEGER NOT NULL DEFAULT 0;
@@ -4,6 +4,7 @@
  */
 use crate::checks::*;
 use crate::prelude::*;
+use crate::util::moderation::{log_mod_action, ModAction, ModActionType};
 
 #[command]
 #[description = "Adds a badge to a user"]
@@ -48,6 +49,21 @@ async fn addbadge(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
         return Err(CommandError(e.to_string()));
     }
 
+    if let Some(guild) = msg.guild_id {
+        let action = ModAction {
+            target: Some(target),
+            moderator: msg.author.clone(),
+            action_type: ModActionType::BadgeAdd,
+            reason: None,
+            details: None,
+            guild,
+            case_id: None,
+            old_value: None,
+            new_value: Some(badge.to_string()),
+        };
+        log_mod_action(action, ctx).await;
+    }
+
     Ok(())
 }
 
@@ -108,6 +124,21 @@ async fn delbadge(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult
             }).await {
                 return Err(CommandError(err.to_string()));
             }
+
+            if let Some(guild) = msg.guild_id {
+                let action = ModAction {
+                    target: Some(target_user),
+                    moderator: msg.author.clone(),
+                    action_type: ModActionType::BadgeRemove,
+                    reason: None,
+                    details: None,
+                    guild,
+                    case_id: None,
+                    old_value: Some(target_badge.to_string()),
+                    new_value: None,
+                };
+                log_mod_action(action, ctx).await;
+            }
         },
     }
 
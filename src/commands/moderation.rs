@@ -6,29 +6,29 @@
 use log::{debug, error, info, warn};
 
 use serenity::framework::standard::{macros::command, Args, CommandError, CommandResult};
-use serenity::model::id::UserId;
+use serenity::model::id::{ChannelId, GuildId, RoleId, UserId};
 use serenity::utils::Colour;
 use serenity::{
-    model::channel::{ChannelType, Message},
+    model::channel::{Message, ReactionType},
     model::user::User,
     prelude::*,
 };
 
-use std::collections::HashMap;
+use std::time::Duration;
 
 use crate::checks::*;
 
-use std::cmp::Ordering;
-
 use crate::util::{
-    data::{
-        get_global_pickle_database, get_pickle_database,
-        
-    },
     moderation::*,
+    raid::*,
+    verification::get_verified,
+    wordfilter::*,
 };
 use crate::prelude::*;
 use crate::models::*;
+use crate::util::{parse_duration, truncate_chars, truncate_field, EMBED_FIELD_LIMIT};
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::PgPool;
 
 struct Strike {
     user: UserId,
@@ -36,109 +36,122 @@ struct Strike {
     moderator: UserId,
 }
 
-#[derive(Eq, Hash)]
-struct StrikeLog {
-    user: UserId,
-    reason: String,
-    moderator: UserId,
-    case_id: String,
-}
-
-struct DscBan {
-    userid: String,
-    reason: String,
-}
-
-impl Ord for StrikeLog {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.case_id
-            .parse::<i32>()
-            .unwrap()
-            .cmp(&other.case_id.parse::<i32>().unwrap())
-    }
-}
-impl PartialOrd for StrikeLog {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(other))
-    }
-}
-impl PartialEq for StrikeLog {
-    fn eq(&self, other: &Self) -> bool {
-        self.case_id.parse::<i32>().unwrap() == other.case_id.parse::<i32>().unwrap()
-    }
-}
-
 #[command]
-#[description = "Adds a strike to the mentioned user"]
+#[description = "Adds a strike to the mentioned user. Details can be added after a `|`, and `--expires <Duration>` (e.g. `30d`) makes the strike stop counting after that time."]
 #[only_in(guilds)]
-#[usage("<@User> <Reason>")]
+#[usage("<@User> [--expires <Duration>] <Reason> [| <Details>]")]
 #[min_args(2)]
 #[checks(Moderator)]
 async fn strike(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let bot_data = &ctx.data.read().await;
     let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let guild_id = msg.guild_id.unwrap();
+    let target = args.single::<UserId>()?;
+
+    let mut expires_at: Option<DateTime<Utc>> = None;
+    if args.current() == Some("--expires") {
+        args.advance();
+        let duration = match args.single::<String>().ok().and_then(|d| parse_duration(&d)) {
+            Some(d) => d,
+            None => {
+                msg.channel_id
+                    .say(&ctx.http, "Invalid expiry. Use a duration such as `30d`, `12h` or `2w`.")
+                    .await?;
+                return Ok(());
+            }
+        };
+        expires_at = Some(Utc::now() + duration);
+    }
+
+    let (reason, details) = match args.rest().splitn(2, '|').collect::<Vec<&str>>().as_slice() {
+        [r, d] => (r.trim().to_string(), Some(d.trim().to_string())),
+        [r] => (r.trim().to_string(), None),
+        _ => (String::new(), None),
+    };
+    if reason.is_empty() {
+        msg.channel_id
+            .say(&ctx.http, "Please provide a reason for the strike.")
+            .await?;
+        return Ok(());
+    }
+
     let strike = Strike {
-        user: args.parse::<UserId>().unwrap(),
-        reason: Some(String::from(args.advance().rest())),
+        user: target,
+        reason: Some(reason),
         moderator: msg.author.clone().into(),
     };
-    sqlx::query!(
-            "INSERT INTO strikes (userid, reason, moderator) VALUES ($1, $2, $3)",
+    let case_id = sqlx::query!(
+            "INSERT INTO strikes (userid, reason, moderator, details, guild_id, expires_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id",
             
                 strike.user.as_u64().to_string(),
                 strike.reason,
-                strike.moderator.as_u64().to_string()
+                strike.moderator.as_u64().to_string(),
+                details,
+                guild_id.as_u64().to_string(),
+                expires_at
             
-        ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+        ).fetch_one(pg_pool).await.map_err(|e| CommandError(e.to_string()))?.id;
 
     msg.channel_id
-        .say(&ctx.http, "Struck the user.")
-        .await
-        .unwrap();
+        .say(&ctx.http, format!("Struck the user. (Case #{})", case_id))
+        .await?;
     let action = ModAction {
-        target: strike.user,
+        target: Some(strike.user),
         moderator: msg.author.clone(),
         action_type: ModActionType::Strike,
         reason: strike.reason,
-        details: None,
-        guild: msg.guild_id.unwrap(),
+        details,
+        guild: guild_id,
+        case_id: Some(case_id),
+        old_value: None,
+        new_value: expires_at.map(|t| format!("Expires {}", t.date().naive_utc())),
     };
-    log_mod_action(action, ctx);
+    log_mod_action(action, ctx).await;
+
+    if let Err(err) = escalate_strikes(ctx, pg_pool, guild_id, target).await {
+        error!("Error escalating strikes for {}: {}", target.as_u64(), err);
+    }
 
     Ok(())
 }
 
 #[command]
-#[description = "Displays a list of strikes given to a user"]
+#[description = "Displays a list of strikes given to a user in this server. Expired strikes are only shown with `--all`."]
 #[only_in(guilds)]
+#[usage("<@User> [--all]")]
 #[min_args(1)]
 #[checks(Moderator)]
-async fn strikelog(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+async fn strikelog(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let bot_data = &ctx.data.read().await;
     let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
-    let target_user = args.parse::<UserId>().unwrap();
-    let mut strikes: Vec<StrikeLog> = Vec::new();
-    {
-        let result = sqlx::query_as!(crate::models::StrikeLog, "SELECT reason,moderator,id FROM strikes WHERE userid = $1", target_user.as_u64().to_string()).fetch_all(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
-
-
-        for strike in result.iter() {
-            let reason = strike.reason.clone();
-            let moderator = strike.moderator.clone();
-            let case_id = strike.id;
-            let strike = StrikeLog {
-                user: target_user,
-                moderator: moderator.parse::<u64>().unwrap().into(),
-                reason: reason,
-                case_id: case_id.to_string(),
-            };
-            strikes.push(strike);
-        }
-    }
+    let target_user = args.single::<UserId>()?;
+    let show_all = args.current() == Some("--all");
+    let result = if show_all {
+        sqlx::query_as!(crate::models::StrikeLog, "SELECT id,moderator,reason,details,is_withdrawn,created_at,expires_at FROM strikes WHERE userid = $1 AND guild_id = $2 ORDER BY id", target_user.as_u64().to_string(), msg.guild_id.unwrap().as_u64().to_string()).fetch_all(pg_pool).await.map_err(|e| CommandError(e.to_string()))?
+    } else {
+        sqlx::query_as!(crate::models::StrikeLog, "SELECT id,moderator,reason,details,is_withdrawn,created_at,expires_at FROM strikes WHERE userid = $1 AND guild_id = $2 AND (expires_at IS NULL OR expires_at > NOW()) ORDER BY id", target_user.as_u64().to_string(), msg.guild_id.unwrap().as_u64().to_string()).fetch_all(pg_pool).await.map_err(|e| CommandError(e.to_string()))?
+    };
+
     let mut result_vec: Vec<(String, String, bool)> = Vec::new();
 
-    for (_i, r) in strikes.iter().enumerate() {
-        result_vec.push((format!("Case #{}", r.case_id), r.reason.clone(), false));
+    for r in result.iter() {
+        let mut line = if r.is_withdrawn {
+            format!("~~{}~~", r.reason)
+        } else {
+            r.reason.clone()
+        };
+        if let Some(d) = &r.details {
+            line.push_str(&format!("\n*{}*", d));
+        }
+        line.push_str(&format!("\nIssued {}", r.created_at.date().naive_utc()));
+        if let Some(expiry) = r.expires_at {
+            if expiry > Utc::now() {
+                line.push_str(&format!(", expires {}", expiry.date().naive_utc()));
+            } else {
+                line.push_str(&format!(", expired {}", expiry.date().naive_utc()));
+            }
+        }
+        result_vec.push((format!("Case #{}", r.id), line, false));
     }
 
     let target_user_name = &target_user.to_user(&ctx.http).await.unwrap().name;
@@ -149,6 +162,9 @@ async fn strikelog(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
                 title.push_str(&target_user_name);
                 e.title(title);
 
+                if result_vec.is_empty() {
+                    e.description("No strikes found.");
+                }
                 e.fields(result_vec);
 
                 let mut footer = String::from("Requested by ");
@@ -170,21 +186,32 @@ async fn strikelog(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 
 #[command]
 #[description = "Manages the bad words filter"]
-#[sub_commands(add)]
+#[sub_commands(add, allow, list, remove, test, import, export, exempt, unexempt)]
 async fn wordfilter(_ctx: &Context, _msg: &Message) -> CommandResult {
     Ok(())
 }
 
 #[command]
-#[description = "Adds a word to the bad words list"]
+#[description = "Adds a word to the bad words list. Entries match whole words unless `--wildcard` (`*` matches any letters), `--regex` or `--contains` is given."]
+#[usage("[--wildcard | --regex | --contains] <Word>")]
 #[checks(Moderator)]
 #[sub_commands(global)]
 async fn add(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    msg.reply(&ctx.http, "Called word management").await?;
     let guild = &msg.guild_id.unwrap();
-    let mut db = get_pickle_database(guild.as_u64(), "banned_words.db");
-    match db.get::<i32>(&args.rest()) {
-        Some(_i) => {
+    let entry = match parse_filter_entry(args.rest(), false) {
+        Ok(e) => e,
+        Err(err) => {
+            msg.channel_id.say(&ctx.http, err).await?;
+            return Ok(());
+        }
+    };
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let added = add_filter_entry(pg_pool, Some(*guild), &entry)
+        .await
+        .map_err(CommandError)?;
+    match added {
+        false => {
             msg.channel_id
                 .send_message(&ctx, |m| {
                     m.embed(|e| {
@@ -199,19 +226,18 @@ async fn add(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
                 })
                 .await?;
         }
-        None => {
-            if let Err(err) = db.set(&args.rest(), &1) {
-                error!("Failed to add local banned word: {:?}", err);
-            };
+        true => {
+            invalidate_filter(ctx, Some(*guild)).await;
             msg.channel_id
                 .send_message(&ctx, |m| {
                     m.embed(|e| {
                         e.title("Word Filter");
 
                         let mut description = String::from("Added ");
-                        description.push_str(&args.rest());
+                        description.push_str(&entry.word);
                         description.push_str(" to the server word filter");
                         e.description(description);
+                        e.field("Matching", entry.mode.name(), true);
                         e.colour(Colour::DARK_GREEN);
 
                         e
@@ -220,6 +246,19 @@ async fn add(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
                     m
                 })
                 .await?;
+
+            let action = ModAction {
+                target: None,
+                moderator: msg.author.clone(),
+                action_type: ModActionType::WordFilterAdd,
+                reason: None,
+                details: Some(format!("Server word filter ({} match)", entry.mode.name())),
+                guild: *guild,
+                case_id: None,
+                old_value: None,
+                new_value: Some(entry.word.clone()),
+            };
+            log_mod_action(action, ctx).await;
         }
     }
 
@@ -227,21 +266,49 @@ async fn add(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 }
 
 #[command]
-#[description = "Adds a word to the global list"]
+#[description = "Adds a word to the global list. Takes the same matching flags as `wordfilter add`."]
+#[usage("[--wildcard | --regex | --contains] <Word>")]
 #[owners_only]
 async fn global(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let mut db = get_global_pickle_database("banned_words.db");
+    let entry = match parse_filter_entry(args.rest(), true) {
+        Ok(e) => e,
+        Err(err) => {
+            msg.channel_id.say(&ctx.http, err).await?;
+            return Ok(());
+        }
+    };
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let added = add_filter_entry(pg_pool, None, &entry)
+        .await
+        .map_err(CommandError)?;
+    if !added {
+        msg.channel_id
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Word Filter");
+                    e.description("That word is already on the global word filter!");
+                    e.colour(Colour::RED);
+
+                    e
+                });
 
-    db.set(args.rest(), &1)?;
+                m
+            })
+            .await?;
+        return Ok(());
+    }
+    invalidate_filter(ctx, None).await;
 
     msg.channel_id
         .send_message(&ctx.http, |m| {
             m.embed(|e| {
                 e.title("Banned Words List");
                 let mut description = String::from("Added ");
-                description.push_str(args.rest());
+                description.push_str(&entry.word);
                 description.push_str(" to the global word filter");
                 e.description(description);
+                e.field("Matching", entry.mode.name(), true);
                 e.footer(|f| {
                     let mut footer = String::from("Requested by ");
                     footer.push_str(&msg.author.name);
@@ -257,176 +324,176 @@ async fn global(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
         })
         .await?;
 
-    warn!("Added a global banned word: {}", args.rest());
+    warn!("Added a global banned word: {}", entry.word);
+
+    if let Some(guild) = msg.guild_id {
+        let action = ModAction {
+            target: None,
+            moderator: msg.author.clone(),
+            action_type: ModActionType::WordFilterAdd,
+            reason: None,
+            details: Some(format!("Global word filter ({} match)", entry.mode.name())),
+            guild,
+            case_id: None,
+            old_value: None,
+            new_value: Some(entry.word.clone()),
+        };
+        log_mod_action(action, ctx).await;
+    }
 
     Ok(())
 }
 
 #[command]
-#[description = "Clears *all* of a users strikes."]
-#[usage("<User>")]
-#[checks(Moderator)]
+#[description = "Exempts a word from the word filter in this server, e.g. `class` or `scunthorpe`"]
+#[usage("<Word>")]
+#[min_args(1)]
 #[only_in(guilds)]
-async fn clearstrikes(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let bot_data = &ctx.data.read().await;
+#[checks(Moderator)]
+async fn allow(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let word = args.rest().trim().to_lowercase();
+    let bot_data = ctx.data.read().await;
     let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    if !allow_word(pg_pool, guild, &word).await.map_err(CommandError)? {
+        msg.channel_id
+            .say(&ctx.http, "That word is already allowed!")
+            .await?;
+        return Ok(());
+    }
+    invalidate_filter(ctx, Some(guild)).await;
 
-    let target = args.parse::<UserId>().unwrap();
-    sqlx::query!(
-        "DELETE FROM strikes WHERE userid = $1",
-        target.as_u64().to_string(),
-    ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
-    let action = ModAction {
-        target,
-        moderator: msg.author.clone(),
-        action_type: ModActionType::ClearStrikes,
-        reason: None,
-        details: None,
-        guild: msg.guild_id.unwrap(),
-    };
-    log_mod_action(action, ctx);
-
-    let target_user_name = target.to_user(&ctx.http).await.unwrap().name;
-
-    match msg
-        .channel_id
+    msg.channel_id
         .send_message(&ctx, |m| {
             m.embed(|e| {
-                e.title("Moderation Subsystem");
-                e.description(format!("Cleared strikes for {}", target_user_name));
-                e.footer(|f| {
-                    f.text(format!("Requested by {}", &msg.author.name));
-
-                    f
-                });
+                e.title("Word Filter");
+                e.description(format!("{} is now exempt from the word filter in this server", word));
                 e.colour(Colour::DARK_GREEN);
+
                 e
             });
 
             m
         })
-        .await
-    {
-        Err(err) => error!("Error sending clearstrike response: {:?}", err),
-        Ok(_msg) => (),
-    }
+        .await?;
+
+    let action = ModAction {
+        target: None,
+        moderator: msg.author.clone(),
+        action_type: ModActionType::WordFilterAllow,
+        reason: None,
+        details: Some(String::from("Server word filter allowlist")),
+        guild,
+        case_id: None,
+        old_value: None,
+        new_value: Some(word),
+    };
+    log_mod_action(action, ctx).await;
 
     Ok(())
 }
 
-#[command]
-#[description = "Modifies a current strike"]
-#[usage("<Case Number> <Thing to modify> <What to modify it to>")]
-#[min_args(3)]
-#[checks(Moderator)]
-#[only_in(guilds)]
-async fn modstrike(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let bot_data = &ctx.data.read().await;
-    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
-
-    let case_id = &args.single::<i32>()?;
-    let modify_thing = &args.single::<String>().unwrap().to_lowercase();
-    let new_value = args.rest();
+/// The guild whose list a word filter command edits, or `None` for the global list.
+fn filter_list_guild(msg: &Message, global: bool) -> Option<GuildId> {
+    if global {
+        None
+    } else {
+        msg.guild_id
+    }
+}
 
-    if modify_thing == "reason" {
-        sqlx::query!(
-            "UPDATE strikes SET reason = $1 WHERE id = $2",
-            new_value, *case_id,
-        ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
-        msg.channel_id
-            .send_message(&ctx, |m| {
-                m.embed(|e| {
-                    e.title("Moderation");
-                    e.description(format!(
-                        "Successfully modified case {}!",
-                        case_id.to_string()
-                    ));
-                    e.field("Field", "Reason", true);
-                    e.field("New Value", new_value, true);
-                    e.colour(Colour::DARK_GREEN);
-                    e.footer(|f| {
-                        f.text(format!("Requested by {}", &msg.author.name));
-                        f
-                    });
-                    e
-                });
-                m
-            })
-            .await?;
-    } else if modify_thing == "withdraw" {
-        sqlx::query!(
-            "UPDATE strikes SET is_withdrawn = 't' WHERE id = $1",
-            *case_id,
-        ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
-        msg.channel_id
-            .send_message(&ctx, |m| {
-                m.embed(|e| {
-                    e.title("Moderation");
-                    e.description(format!("Sucessfully withdrew case #{}", case_id));
-                    e.colour(Colour::DARK_GREEN);
-                    e.footer(|f| {
-                        f.text(format!("Requested by {}", &msg.author.name));
-                        f
-                    });
-                    e
-                });
-                m
-            })
-            .await?;
+fn filter_list_name(global: bool) -> &'static str {
+    if global {
+        "global word filter"
     } else {
-        msg.channel_id
-            .send_message(&ctx, |m| {
-                m.embed(|e| {
-                    e.title("Moderation");
-                    e.description("You can only modify a strike's reason.");
-                    e.colour(Colour::RED);
-                    e.footer(|f| {
-                        f.text(format!("Requested by {}", &msg.author.name));
-                        f
-                    });
-                    e
-                });
-                m
-            })
-            .await?;
+        "server word filter"
     }
+}
 
-    Ok(())
+/// Logs a word filter change to the invoking guild. Global list changes made in DMs have
+/// no guild to log to.
+async fn log_filter_change(
+    ctx: &Context,
+    msg: &Message,
+    action_type: ModActionType,
+    details: String,
+    old_value: Option<String>,
+    new_value: Option<String>,
+) {
+    if let Some(guild) = msg.guild_id {
+        let action = ModAction {
+            target: None,
+            moderator: msg.author.clone(),
+            action_type,
+            reason: None,
+            details: Some(details),
+            guild,
+            case_id: None,
+            old_value,
+            new_value,
+        };
+        log_mod_action(action, ctx).await;
+    }
 }
 
-#[command]
-#[usage("<Case Number>")]
-#[num_args(1)]
-#[checks(Moderator)]
-#[only_in(guilds)]
-async fn getstrike(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let bot_data = &ctx.data.read().await;
+async fn send_filter_list(ctx: &Context, msg: &Message, global: bool) -> CommandResult {
+    let bot_data = ctx.data.read().await;
     let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
-
-
-    let result = sqlx::query_as!(crate::models::GetStrike, "SELECT userid,moderator,reason,is_withdrawn FROM strikes WHERE id = $1", args.current().unwrap().parse::<i32>().unwrap()).fetch_one(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
-    
-    let user_id = UserId(result.userid.parse::<u64>().unwrap());
-
-    let user = match user_id.to_user(&ctx.http).await {
-        Ok(u) => u,
-        Err(err) => return Err(CommandError(err.to_string())),
+    let entries = match filter_list_guild(msg, global) {
+        None => get_global_filter_entries(pg_pool).await,
+        Some(guild) => get_guild_filter_entries(pg_pool, guild).await,
+    }
+    .map_err(CommandError)?;
+    let allowed = match filter_list_guild(msg, global) {
+        None => Vec::new(),
+        Some(guild) => get_allowed_words(pg_pool, guild).await.map_err(CommandError)?,
     };
 
-    let moderator_id: UserId = result.moderator.parse::<u64>().unwrap().into();
-
-    let moderator = moderator_id.to_user(&ctx.http).await?;
+    let mut entry_list = String::from("‎"); // Contains a unicode "blank space" to appease JSON
+    for entry in entries.iter() {
+        let line = format!("`{}` ({})\n", entry.word, entry.mode.name());
+        if entry_list.len() + line.len() > 1000 {
+            entry_list.push_str("... use `wordfilter export` for the full list");
+            break;
+        }
+        entry_list.push_str(&line);
+    }
+    let mut allowed_list = String::from("‎"); // Contains a unicode "blank space" to appease JSON
+    for word in allowed.iter() {
+        let line = format!("`{}`\n", word);
+        if allowed_list.len() + line.len() > 1000 {
+            allowed_list.push_str("...");
+            break;
+        }
+        allowed_list.push_str(&line);
+    }
+    let exemptions: Vec<String> = match filter_list_guild(msg, global) {
+        None => Vec::new(),
+        Some(guild) => get_exemptions(pg_pool, guild)
+            .await
+            .map_err(CommandError)?
+            .iter()
+            .map(|(id, kind)| format_exemption(id, kind))
+            .collect(),
+    };
 
     msg.channel_id
-        .send_message(&ctx, |m| {
+        .send_message(&ctx.http, |m| {
             m.embed(|e| {
-                e.title("Moderation Case");
-                e.description(result.reason);
-                e.fields(vec![
-                    ("User", &user.name, true),
-                    ("Moderator", &moderator.name, true),
-                    ("Is Withdrawn?", &result.is_withdrawn.to_string(), true),
-                ]);
+                e.title("Word Filter");
+                e.field(
+                    format!("Entries in the {} ({})", filter_list_name(global), entries.len()),
+                    &entry_list,
+                    false,
+                );
+                if !global {
+                    e.field(format!("Allowed words ({})", allowed.len()), &allowed_list, false);
+                    e.field(
+                        format!("Exempt roles and channels ({})", exemptions.len()),
+                        format!("‎{}", exemptions.join(" ")),
+                        false,
+                    );
+                }
                 e.footer(|f| {
                     f.text(format!("Requested by {}", &msg.author.name));
                     f
@@ -435,50 +502,768 @@ async fn getstrike(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
             });
             m
         })
-        .await;
-    return Ok(());
+        .await?;
+
+    Ok(())
 }
 
-#[command]
-#[usage("<@Mention>")]
-#[description = "Checks a user against the banlist and returns other information"]
-#[num_args(1)]
-#[only_in(guilds)]
-async fn runuser(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let bot_data = &ctx.data.read().await;
+async fn remove_filter_entry(ctx: &Context, msg: &Message, word: &str, global: bool) -> CommandResult {
+    let word = word.trim();
+    let bot_data = ctx.data.read().await;
     let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
-    let age_db = get_global_pickle_database("age.db");
-    let target_id = match args.parse::<UserId>() {
-        Ok(id) => id,
-        Err(err) => {
-            error!(
-                "Error parsing userid for `runuser` command in {}: {:?}",
-                &msg.guild_id.unwrap(),
-                err
-            );
-            return Err(CommandError(err.to_string()));
+    let list_guild = filter_list_guild(msg, global);
+    let removed_from = if delete_filter_entry(pg_pool, list_guild, word)
+        .await
+        .map_err(CommandError)?
+    {
+        filter_list_name(global)
+    } else if let Some(guild) = list_guild {
+        // Not a filter entry, so try the server's allowlist instead
+        if !disallow_word(pg_pool, guild, &word.to_lowercase())
+            .await
+            .map_err(CommandError)?
+        {
+            msg.channel_id
+                .say(&ctx.http, "That word isn't on the word filter or its allowlist.")
+                .await?;
+            return Ok(());
         }
-    };
-
+        "server word filter allowlist"
+    } else {
+        msg.channel_id
+            .say(&ctx.http, "That word isn't on the global word filter.")
+            .await?;
+        return Ok(());
+    };
+    invalidate_filter(ctx, list_guild).await;
+
+    msg.channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Word Filter");
+                e.description(format!("Removed {} from the {}", word, removed_from));
+                e.colour(Colour::DARK_GREEN);
+
+                e
+            });
+
+            m
+        })
+        .await?;
+
+    if global {
+        warn!("Removed a global banned word: {}", word);
+    }
+    log_filter_change(
+        ctx,
+        msg,
+        ModActionType::WordFilterRemove,
+        removed_from.to_string(),
+        Some(word.to_string()),
+        None,
+    )
+    .await;
+
+    Ok(())
+}
+
+async fn import_filter_entries(ctx: &Context, msg: &Message, global: bool) -> CommandResult {
+    let attachment = match msg.attachments.get(0) {
+        Some(a) => a,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "Please attach a text file with one entry per line.")
+                .await?;
+            return Ok(());
+        }
+    };
+    let contents = attachment.download().await?;
+    let contents = match String::from_utf8(contents) {
+        Ok(c) => c,
+        Err(_) => {
+            msg.channel_id
+                .say(&ctx.http, "The attached file isn't a text file.")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let list_guild = filter_list_guild(msg, global);
+    let mut added = 0;
+    let mut skipped = 0;
+    let mut invalid: Vec<String> = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_filter_entry(line, global) {
+            Ok(entry) => {
+                if add_filter_entry(pg_pool, list_guild, &entry)
+                    .await
+                    .map_err(CommandError)?
+                {
+                    added += 1;
+                } else {
+                    skipped += 1;
+                }
+            }
+            Err(err) => invalid.push(format!("`{}`: {}", line, err)),
+        }
+    }
+    invalidate_filter(ctx, list_guild).await;
+
+    let mut invalid_list = String::from("‎"); // Contains a unicode "blank space" to appease JSON
+    for line in invalid.iter() {
+        if invalid_list.len() + line.len() > 1000 {
+            invalid_list.push_str("...");
+            break;
+        }
+        invalid_list.push_str(line);
+        invalid_list.push('\n');
+    }
+
+    msg.channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Word Filter Import");
+                e.description(format!("Imported {} into the {}", attachment.filename, filter_list_name(global)));
+                e.field("Added", added, true);
+                e.field("Already filtered", skipped, true);
+                if !invalid.is_empty() {
+                    e.field(format!("Invalid ({})", invalid.len()), &invalid_list, false);
+                }
+                e.colour(Colour::DARK_GREEN);
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await?;
+
+    if global {
+        warn!("Imported {} global banned words", added);
+    }
+    log_filter_change(
+        ctx,
+        msg,
+        ModActionType::WordFilterImport,
+        format!(
+            "{} entries added to the {} from {}",
+            added,
+            filter_list_name(global),
+            attachment.filename
+        ),
+        None,
+        None,
+    )
+    .await;
+
+    Ok(())
+}
+
+async fn export_filter_entries(ctx: &Context, msg: &Message, global: bool) -> CommandResult {
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let entries = match filter_list_guild(msg, global) {
+        None => get_global_filter_entries(pg_pool).await,
+        Some(guild) => get_guild_filter_entries(pg_pool, guild).await,
+    }
+    .map_err(CommandError)?;
+    let contents = entries
+        .iter()
+        .map(format_filter_entry)
+        .collect::<Vec<String>>()
+        .join("\n");
+    let filename = if global {
+        String::from("global_wordfilter.txt")
+    } else {
+        format!("wordfilter_{}.txt", msg.guild_id.unwrap().as_u64())
+    };
+
+    msg.channel_id
+        .send_files(&ctx.http, vec![(contents.as_bytes(), filename.as_str())], |m| {
+            m.content(format!(
+                "Exported {} entries from the {}",
+                entries.len(),
+                filter_list_name(global)
+            ));
+            m
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Lists the entries in the server word filter, its allowlist and exemptions"]
+#[only_in(guilds)]
+#[checks(Moderator)]
+#[sub_commands(list_global)]
+async fn list(ctx: &Context, msg: &Message) -> CommandResult {
+    send_filter_list(ctx, msg, false).await
+}
+
+#[command("global")]
+#[description = "Lists the entries in the global word filter"]
+#[owners_only]
+async fn list_global(ctx: &Context, msg: &Message) -> CommandResult {
+    send_filter_list(ctx, msg, true).await
+}
+
+#[command]
+#[description = "Removes a word from the server word filter, or from its allowlist"]
+#[usage("<Word>")]
+#[min_args(1)]
+#[only_in(guilds)]
+#[checks(Moderator)]
+#[sub_commands(remove_global)]
+async fn remove(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    remove_filter_entry(ctx, msg, args.rest(), false).await
+}
+
+#[command("global")]
+#[description = "Removes a word from the global word filter"]
+#[usage("<Word>")]
+#[min_args(1)]
+#[owners_only]
+async fn remove_global(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    remove_filter_entry(ctx, msg, args.rest(), true).await
+}
+
+#[command]
+#[description = "Checks a message against the word filter and shows which entry it matches"]
+#[usage("<Text>")]
+#[min_args(1)]
+#[only_in(guilds)]
+#[checks(Moderator)]
+async fn test(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let matched = find_banned_word(ctx, args.rest(), guild)
+        .await
+        .map_err(CommandError)?;
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Word Filter Test");
+                e.field("Normalised text", normalize(args.rest()), false);
+                match &matched {
+                    Some(entry) => {
+                        e.description("This message would be filtered.");
+                        e.field("Matched entry", format!("`{}`", entry.word), true);
+                        e.field("Matching", entry.mode.name(), true);
+                        e.field("List", filter_list_name(entry.global), true);
+                        e.colour(Colour::RED);
+                    }
+                    None => {
+                        e.description("This message would not be filtered.");
+                        e.colour(Colour::DARK_GREEN);
+                    }
+                }
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Adds every entry in an attached text file to the server word filter. Each line takes the same form as `wordfilter add`."]
+#[only_in(guilds)]
+#[checks(Moderator)]
+#[sub_commands(import_global)]
+async fn import(ctx: &Context, msg: &Message) -> CommandResult {
+    import_filter_entries(ctx, msg, false).await
+}
+
+#[command("global")]
+#[description = "Adds every entry in an attached text file to the global word filter"]
+#[owners_only]
+async fn import_global(ctx: &Context, msg: &Message) -> CommandResult {
+    import_filter_entries(ctx, msg, true).await
+}
+
+#[command]
+#[description = "Exports the server word filter as a text file that `wordfilter import` accepts"]
+#[only_in(guilds)]
+#[checks(Moderator)]
+#[sub_commands(export_global)]
+async fn export(ctx: &Context, msg: &Message) -> CommandResult {
+    export_filter_entries(ctx, msg, false).await
+}
+
+#[command("global")]
+#[description = "Exports the global word filter as a text file"]
+#[owners_only]
+async fn export_global(ctx: &Context, msg: &Message) -> CommandResult {
+    export_filter_entries(ctx, msg, true).await
+}
+
+/// Reads a role or channel mention (or a bare ID) for `wordfilter exempt`.
+async fn parse_exemption(ctx: &Context, guild: GuildId, input: &str) -> Option<(u64, &'static str)> {
+    let input = input.trim();
+    if input.starts_with("<@&") {
+        return input.parse::<RoleId>().ok().map(|r| (*r.as_u64(), "role"));
+    }
+    if input.starts_with("<#") {
+        return input.parse::<ChannelId>().ok().map(|c| (*c.as_u64(), "channel"));
+    }
+    let id = input.parse::<u64>().ok()?;
+    match guild.roles(&ctx.http).await {
+        Ok(roles) if roles.contains_key(&RoleId(id)) => Some((id, "role")),
+        _ => Some((id, "channel")),
+    }
+}
+
+fn format_exemption(id: &str, kind: &str) -> String {
+    match kind {
+        "role" => format!("<@&{}>", id),
+        _ => format!("<#{}>", id),
+    }
+}
+
+#[command]
+#[description = "Stops the word filter and spam detection from acting in a channel or on members with a role"]
+#[usage("<@Role | #Channel>")]
+#[min_args(1)]
+#[only_in(guilds)]
+#[checks(Moderator)]
+async fn exempt(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let (id, kind) = match parse_exemption(ctx, guild, args.rest()).await {
+        Some(e) => e,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "Please mention a role or channel.")
+                .await?;
+            return Ok(());
+        }
+    };
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    add_exemption(pg_pool, guild, id, kind)
+        .await
+        .map_err(CommandError)?;
+    invalidate_filter(ctx, Some(guild)).await;
+    let exemption = format_exemption(&id.to_string(), kind);
+
+    msg.channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Word Filter");
+                e.description(format!("{} is now exempt from the word filter", exemption));
+                e.colour(Colour::DARK_GREEN);
+
+                e
+            });
+
+            m
+        })
+        .await?;
+
+    log_filter_change(
+        ctx,
+        msg,
+        ModActionType::WordFilterExempt,
+        format!("Exempt {}", kind),
+        None,
+        Some(exemption),
+    )
+    .await;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Removes a word filter exemption from a channel or role"]
+#[usage("<@Role | #Channel>")]
+#[min_args(1)]
+#[only_in(guilds)]
+#[checks(Moderator)]
+async fn unexempt(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let guild = msg.guild_id.unwrap();
+    let (id, kind) = match parse_exemption(ctx, guild, args.rest()).await {
+        Some(e) => e,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, "Please mention a role or channel.")
+                .await?;
+            return Ok(());
+        }
+    };
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    if !remove_exemption(pg_pool, guild, id)
+        .await
+        .map_err(CommandError)?
+    {
+        msg.channel_id
+            .say(&ctx.http, "That role or channel isn't exempt.")
+            .await?;
+        return Ok(());
+    }
+    invalidate_filter(ctx, Some(guild)).await;
+    let exemption = format_exemption(&id.to_string(), kind);
+
+    msg.channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Word Filter");
+                e.description(format!("{} is no longer exempt from the word filter", exemption));
+                e.colour(Colour::DARK_GREEN);
+
+                e
+            });
+
+            m
+        })
+        .await?;
+
+    log_filter_change(
+        ctx,
+        msg,
+        ModActionType::WordFilterExempt,
+        format!("Exempt {}", kind),
+        Some(exemption),
+        None,
+    )
+    .await;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Clears *all* of a users strikes in this server."]
+#[usage("<User>")]
+#[checks(Moderator)]
+#[only_in(guilds)]
+async fn clearstrikes(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+
+    let target = args.parse::<UserId>().unwrap();
+    sqlx::query!(
+        "DELETE FROM strikes WHERE userid = $1 AND guild_id = $2",
+        target.as_u64().to_string(),
+        msg.guild_id.unwrap().as_u64().to_string(),
+    ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+    let action = ModAction {
+        target: Some(target),
+        moderator: msg.author.clone(),
+        action_type: ModActionType::ClearStrikes,
+        reason: None,
+        details: None,
+        guild: msg.guild_id.unwrap(),
+        case_id: None,
+        old_value: None,
+        new_value: None,
+    };
+    log_mod_action(action, ctx).await;
+
+    let target_user_name = target.to_user(&ctx.http).await.unwrap().name;
+
+    match msg
+        .channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Moderation Subsystem");
+                e.description(format!("Cleared strikes for {}", target_user_name));
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+
+                    f
+                });
+                e.colour(Colour::DARK_GREEN);
+                e
+            });
+
+            m
+        })
+        .await
+    {
+        Err(err) => error!("Error sending clearstrike response: {:?}", err),
+        Ok(_msg) => (),
+    }
+
+    Ok(())
+}
+
+#[command]
+#[description = "Modifies a current strike. You can change the `reason`, `details` or `expires` (a duration, or `never`), or `withdraw` it."]
+#[usage("<Case Number> <Thing to modify> <What to modify it to>")]
+#[min_args(2)]
+#[checks(Moderator)]
+#[only_in(guilds)]
+async fn modstrike(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let guild_id = msg.guild_id.unwrap().as_u64().to_string();
+
+    let case_id = &args.single::<i32>()?;
+    let modify_thing = &args.single::<String>().unwrap().to_lowercase();
+    let new_value = args.rest();
+
+    let current = sqlx::query_as!(crate::models::GetStrike, "SELECT userid,moderator,reason,details,is_withdrawn,created_at,expires_at FROM strikes WHERE id = $1 AND guild_id = $2", *case_id, guild_id).fetch_optional(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+    let current = match current {
+        Some(c) => c,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, format!("Could not find case #{} in this server.", case_id))
+                .await?;
+            return Ok(());
+        }
+    };
+    let format_expiry = |t: Option<DateTime<Utc>>| match t {
+        Some(t) => t.date().naive_utc().to_string(),
+        None => String::from("Never"),
+    };
+
+    let (old_value, logged_value) = if modify_thing == "reason" || modify_thing == "details" {
+        let old_value = if modify_thing == "reason" {
+            sqlx::query!(
+                "UPDATE strikes SET reason = $1 WHERE id = $2 AND guild_id = $3",
+                new_value, *case_id, guild_id,
+            ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+            current.reason.clone()
+        } else {
+            sqlx::query!(
+                "UPDATE strikes SET details = $1 WHERE id = $2 AND guild_id = $3",
+                new_value, *case_id, guild_id,
+            ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+            current.details.clone().unwrap_or_default()
+        };
+        msg.channel_id
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Moderation");
+                    e.description(format!(
+                        "Successfully modified case {}!",
+                        case_id.to_string()
+                    ));
+                    e.field("Field", modify_thing, true);
+                    e.field("New Value", new_value, true);
+                    e.colour(Colour::DARK_GREEN);
+                    e.footer(|f| {
+                        f.text(format!("Requested by {}", &msg.author.name));
+                        f
+                    });
+                    e
+                });
+                m
+            })
+            .await?;
+        (old_value, new_value.to_string())
+    } else if modify_thing == "expires" {
+        let expires_at: Option<DateTime<Utc>> = if new_value == "never" {
+            None
+        } else {
+            match parse_duration(new_value) {
+                Some(d) => Some(Utc::now() + d),
+                None => {
+                    msg.channel_id
+                        .say(&ctx.http, "Invalid expiry. Use a duration such as `30d`, `12h` or `2w`, or `never`.")
+                        .await?;
+                    return Ok(());
+                }
+            }
+        };
+        sqlx::query!(
+            "UPDATE strikes SET expires_at = $1 WHERE id = $2 AND guild_id = $3",
+            expires_at, *case_id, guild_id,
+        ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+        msg.channel_id
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Moderation");
+                    e.description(format!(
+                        "Successfully modified case {}!",
+                        case_id.to_string()
+                    ));
+                    e.field("Field", "Expires", true);
+                    e.field("New Value", format_expiry(expires_at), true);
+                    e.colour(Colour::DARK_GREEN);
+                    e.footer(|f| {
+                        f.text(format!("Requested by {}", &msg.author.name));
+                        f
+                    });
+                    e
+                });
+                m
+            })
+            .await?;
+        (format_expiry(current.expires_at), format_expiry(expires_at))
+    } else if modify_thing == "withdraw" {
+        sqlx::query!(
+            "UPDATE strikes SET is_withdrawn = 't' WHERE id = $1 AND guild_id = $2",
+            *case_id, guild_id,
+        ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+        msg.channel_id
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Moderation");
+                    e.description(format!("Sucessfully withdrew case #{}", case_id));
+                    e.colour(Colour::DARK_GREEN);
+                    e.footer(|f| {
+                        f.text(format!("Requested by {}", &msg.author.name));
+                        f
+                    });
+                    e
+                });
+                m
+            })
+            .await?;
+        (String::from("Active"), String::from("Withdrawn"))
+    } else {
+        msg.channel_id
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Moderation");
+                    e.description("You can only modify a strike's reason, details or expiry, or withdraw it.");
+                    e.colour(Colour::RED);
+                    e.footer(|f| {
+                        f.text(format!("Requested by {}", &msg.author.name));
+                        f
+                    });
+                    e
+                });
+                m
+            })
+            .await?;
+        return Ok(());
+    };
+
+    let action = ModAction {
+        target: current.userid.parse::<u64>().ok().map(UserId),
+        moderator: msg.author.clone(),
+        action_type: ModActionType::ModifyStrike,
+        reason: None,
+        details: Some(format!("Changed {}", modify_thing)),
+        guild: msg.guild_id.unwrap(),
+        case_id: Some(*case_id),
+        old_value: Some(old_value),
+        new_value: Some(logged_value),
+    };
+    log_mod_action(action, ctx).await;
+
+    Ok(())
+}
+
+#[command]
+#[usage("<Case Number>")]
+#[num_args(1)]
+#[checks(Moderator)]
+#[only_in(guilds)]
+async fn getstrike(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+
+
+    let case_id = args.current().unwrap().parse::<i32>()?;
+    let result = sqlx::query_as!(crate::models::GetStrike, "SELECT userid,moderator,reason,details,is_withdrawn,created_at,expires_at FROM strikes WHERE id = $1 AND guild_id = $2", case_id, msg.guild_id.unwrap().as_u64().to_string()).fetch_optional(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+    let result = match result {
+        Some(r) => r,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, format!("Could not find strike #{} in this server.", case_id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let user_id = UserId(result.userid.parse::<u64>().unwrap());
+
+    let user = match user_id.to_user(&ctx.http).await {
+        Ok(u) => u,
+        Err(err) => return Err(CommandError(err.to_string())),
+    };
+
+    let moderator_id: UserId = result.moderator.parse::<u64>().unwrap().into();
+
+    let moderator = moderator_id.to_user(&ctx.http).await?;
+
+    let expires = match result.expires_at {
+        Some(t) => t.date().naive_utc().to_string(),
+        None => String::from("Never"),
+    };
+
+    msg.channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Moderation Case");
+                e.description(result.reason);
+                e.fields(vec![
+                    ("User", &user.name, true),
+                    ("Moderator", &moderator.name, true),
+                    ("Is Withdrawn?", &result.is_withdrawn.to_string(), true),
+                    ("Issued", &result.created_at.date().naive_utc().to_string(), true),
+                    ("Expires", &expires, true),
+                ]);
+                if let Some(d) = &result.details {
+                    e.field("Details", d, false);
+                }
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await;
+    return Ok(());
+}
+
+#[command]
+#[usage("<@Mention>")]
+#[description = "Checks a user against the banlist and returns other information"]
+#[num_args(1)]
+#[only_in(guilds)]
+async fn runuser(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let target_id = match args.parse::<UserId>() {
+        Ok(id) => id,
+        Err(err) => {
+            error!(
+                "Error parsing userid for `runuser` command in {}: {:?}",
+                &msg.guild_id.unwrap(),
+                err
+            );
+            return Err(CommandError(err.to_string()));
+        }
+    };
+
     let mut badges: String = String::from("‎"); // Contains a unicode "blank space" to appease JSON
-    let mut is_banned = false;
 
-    let age_group = age_db.get::<String>(&target_id.as_u64().to_string());
-    
-        let result = sqlx::query_as!(crate::models::Dban, "SELECT userid,reason,guild_id,id,is_withdrawn FROM dbans WHERE userid = $1", target_id.as_u64().to_string()).fetch_one(pg_pool).await;
-        
-        match result {
-          Ok(_) => {
-            is_banned = true;
-          },
-          Err(_) => {
-            is_banned = false;
-          }
-            
-        }
-    
+    let age_group = sqlx::query!(
+        "SELECT age_group FROM age_groups WHERE userid = $1",
+        target_id.as_u64().to_string()
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .map_err(|e| CommandError(e.to_string()))?
+    .map(|r| r.age_group);
+
+        // DSC Bans
+        let dbans = get_active_dbans(pg_pool, target_id).await.map_err(CommandError)?;
+        let ban_sources = count_ban_sources(&dbans);
+        let ban_list = describe_dbans(ctx, &dbans).await;
+
+        // Advisories
+        let active_advisories = sqlx::query!("SELECT COUNT(*) AS count FROM advisories WHERE userid = $1 AND is_withdrawn = 'f'", target_id.as_u64().to_string()).fetch_one(pg_pool).await.map_err(|e| CommandError(e.to_string()))?.count.unwrap_or(0);
 
-    
         // Badges
         let result = sqlx::query_as!(Badge, "SELECT badge FROM badges WHERE userid = $1", target_id.as_u64().to_string()).fetch_all(pg_pool).await.unwrap_or_else(|_| vec![Badge::default()]);
         
@@ -486,34 +1271,18 @@ async fn runuser(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
           badges.push_str(&format!("{}", badge.badge));
         }    
     // Verified Roles
-    //* Create the pickledb instance here, and then add it to the db_map later with the right key.
-    let eagle_db = get_global_pickle_database("eagle.db");
-    let summit_db = get_global_pickle_database("summit.db");
-    let cstaff_db = get_global_pickle_database("campstaff.db");
-    let ypt_db = get_global_pickle_database("ypt.db");
-    let ordeal_db = get_global_pickle_database("ordeal.db");
-    let brotherhood_db = get_global_pickle_database("brotherhood.db");
-    let vigil_db = get_global_pickle_database("vigil.db");
-    let qm_db = get_global_pickle_database("quartermaster.db");
-    let mut db_map: HashMap<&str, pickledb::PickleDb> = HashMap::new();
-
-    db_map.insert("Eagle", eagle_db);
-    db_map.insert("Summit", summit_db);
-    db_map.insert("Camp Staff", cstaff_db);
-    db_map.insert("YPT", ypt_db);
-    db_map.insert("Ordeal", ordeal_db);
-    db_map.insert("Brotherhood", brotherhood_db);
-    db_map.insert("Vigil", vigil_db);
-    db_map.insert("Quartermaster", qm_db);
-
+    let verified = get_verified(pg_pool, target_id).await.map_err(CommandError)?;
     let mut verified_roles = String::from("‎"); // Contains a unicode "blank space" to appease JSON
-    for (key, db) in db_map {
-        if let Some(i) = db.get::<i32>(&target_id.as_u64().to_string()) {
-            verified_roles.push_str(&format!("{}\n", key));
-            debug!("Found verified role {}", key);
-        } else {
-            debug!("Did not find verified role {}", key);
+    for (verification, award) in verified.iter() {
+        match verification.expires_at {
+            Some(expires_at) => verified_roles.push_str(&format!(
+                "{} (expires {})\n",
+                award.name,
+                expires_at.date().naive_utc()
+            )),
+            None => verified_roles.push_str(&format!("{}\n", award.name)),
         }
+        debug!("Found verified role {}", award.name);
     }
 
     let target_user = &ctx.http.get_user(*target_id.as_u64()).await?;
@@ -548,8 +1317,11 @@ async fn runuser(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
         .send_message(&ctx, |m| {
             m.embed(|e| {
                 e.title("User Info");
-                if is_banned {
-                    e.description("User has a current ban on a DSC member server.");
+                if ban_sources > 0 {
+                    e.description(format!(
+                        "User has current bans on {} DSC member server(s).",
+                        ban_sources
+                    ));
                     e.colour(Colour::RED);
                 } else {
                     e.description("User is in good standing with DSC.");
@@ -574,9 +1346,13 @@ async fn runuser(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
                         true,
                     ),
                     ("Age Group", age_line, true),
+                    ("Active Advisories", active_advisories.to_string(), true),
                     ("Verified Roles", verified_roles, true),
                     ("DSC Badges", badges, true),
                 ]);
+                if ban_sources > 0 {
+                    e.field("DSC Bans", &ban_list, false);
+                }
 
                 e.footer(|f| {
                     f.text(format!("DSC Bot | Powered by Rusty Developers"));
@@ -598,57 +1374,166 @@ async fn runuser(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 }
 
 #[command]
-#[description = "Sends the server's banlist to the DSC database"]
+#[description = "Syncs the server's banlist with the DSC database. Shows the changes first, and applies them once you react with ✅."]
 #[only_in(guilds)]
 #[checks(Moderator)]
 async fn syncbans(ctx: &Context, msg: &Message) -> CommandResult {
     let bot_data = &ctx.data.read().await;
     let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let guild_id = msg.guild_id.unwrap();
 
-    let mut current_dsc_bans: Vec<DscBan> = Vec::new();
-        let result = sqlx::query_as!(DscBan, "SELECT userid,reason FROM dbans WHERE guild_id = $1", msg.guild_id.unwrap().as_u64().to_string()).fetch_all(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
-        debug!("Getting current bans");
-        
-        for b in result {
-            current_dsc_bans.push(b);
-        }
+    debug!("Getting current bans");
+    let current_dsc_bans = sqlx::query_as!(Dban, "SELECT id,reason,guild_id,userid,is_withdrawn FROM dbans WHERE guild_id = $1", guild_id.as_u64().to_string()).fetch_all(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
     debug!("Getting guild bans");
-    let guild_bans = &ctx.http.get_bans(*msg.guild_id.unwrap().as_u64()).await?;
-    {
-     
+    let guild_bans = &ctx.http.get_bans(*guild_id.as_u64()).await?;
+
+    debug!("Checking server bans against DSC bans");
+    // Guild bans without an active DSC ban, including re-bans of users whose earlier ban was withdrawn
+    let mut to_add: Vec<(String, String, String)> = Vec::new();
+    for b in guild_bans.iter() {
+        let reason: String = match &b.reason {
+            Some(r) => r.clone(),
+            None => String::from("No reason provided"),
+        };
+        if reason.starts_with(AUTO_BAN_PREFIX) {
+            continue;
+        }
+        let b_userid = b.user.id.as_u64().to_string();
+        if !current_dsc_bans.iter().any(|d| !d.is_withdrawn && d.userid == b_userid) {
+            to_add.push((b_userid, format!("{}#{}", b.user.name, b.user.discriminator), reason));
+        }
+    }
+    // Active DSC bans whose guild ban has since been lifted
+    let to_withdraw: Vec<&Dban> = current_dsc_bans
+        .iter()
+        .filter(|d| !d.is_withdrawn)
+        .filter(|d| !guild_bans.iter().any(|b| b.user.id.as_u64().to_string() == d.userid))
+        .collect();
+
+    if to_add.is_empty() && to_withdraw.is_empty() {
+        msg.channel_id
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Ban Sync");
+                    e.description("The server's bans are already in sync with the DSC Banlist");
+                    e.colour(Colour::DARK_GREEN);
+                    e.footer(|f| {
+                        f.text("DSC Bot | Powered by Rusty Development");
+                        f
+                    });
+                    e
+                });
+                m
+            })
+            .await?;
+        return Ok(());
+    }
 
-        debug!("Checking server bans against DSC bans");
-        for b in guild_bans.iter() {
-            let reason: String = match &b.reason {
-                Some(r) => r.clone(),
-                None => String::from("No reason provided"),
-            };
-            let b_userid = b.user.id.as_u64();
-            let b_guildid = *msg.guild_id.unwrap().as_u64();
-            if current_dsc_bans.len() > 0 {
-                for dscb in current_dsc_bans.iter() {
-                    if !b.user.id.as_u64() == dscb.userid.parse::<u64>().unwrap() {
-                        sqlx::query!("INSERT INTO dbans(userid,reason,guild_id) VALUES ($1, $2, $3)",
-                            b_userid.to_string(),
-                            reason,
-                            b_guildid.to_string()
-                        ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
-                    }
-                }
-            } else {
-                sqlx::query!("INSERT INTO dbans(userid,reason,guild_id) VALUES ($1, $2, $3)",
-                    b_userid.to_string(),
-                    reason,
-                    b_guildid.to_string()
-                ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
-            }
+    let mut add_list = String::from("‎"); // Contains a unicode "blank space" to appease JSON
+    for (_id, name, reason) in to_add.iter() {
+        let line = format!("{}: {}\n", name, reason);
+        if add_list.len() + line.len() > 1000 {
+            add_list.push_str("...");
+            break;
         }
+        add_list.push_str(&line);
+    }
+    let mut withdraw_list = String::from("‎"); // Contains a unicode "blank space" to appease JSON
+    for d in to_withdraw.iter() {
+        let line = format!("Case #{} (<@{}>): {}\n", d.id, d.userid, d.reason);
+        if withdraw_list.len() + line.len() > 1000 {
+            withdraw_list.push_str("...");
+            break;
+        }
+        withdraw_list.push_str(&line);
+    }
+
+    let preview = msg
+        .channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Ban Sync Preview");
+                e.description("React with ✅ to apply these changes, or ❌ to cancel.");
+                e.field(format!("Bans to upload ({})", to_add.len()), &add_list, false);
+                e.field(format!("Bans to withdraw ({})", to_withdraw.len()), &withdraw_list, false);
+                e.colour(Colour::ORANGE);
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await?;
+    preview.react(&ctx.http, '✅').await?;
+    preview.react(&ctx.http, '❌').await?;
+
+    let confirmed = match preview
+        .await_reaction(&ctx)
+        .author_id(msg.author.id)
+        .timeout(Duration::from_secs(120))
+        .await
+    {
+        Some(action) => match &action.as_inner_ref().emoji {
+            ReactionType::Unicode(e) => e == "✅",
+            _ => false,
+        },
+        None => false,
+    };
+    if !confirmed {
+        msg.channel_id
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Ban Sync");
+                    e.description("Ban sync cancelled. No changes were made.");
+                    e.colour(Colour::RED);
+                    e
+                });
+                m
+            })
+            .await?;
+        return Ok(());
+    }
+
+    let mut tx = pg_pool.begin().await.map_err(|e| CommandError(e.to_string()))?;
+    for (userid, _name, reason) in to_add.iter() {
+        sqlx::query!("INSERT INTO dbans(userid,reason,guild_id) VALUES ($1, $2, $3)",
+            userid,
+            reason,
+            guild_id.as_u64().to_string()
+        ).execute(&mut tx).await.map_err(|e| CommandError(e.to_string()))?;
+    }
+    for d in to_withdraw.iter() {
+        sqlx::query!("UPDATE dbans SET is_withdrawn = 't' WHERE id = $1", d.id)
+            .execute(&mut tx).await.map_err(|e| CommandError(e.to_string()))?;
     }
+    tx.commit().await.map_err(|e| CommandError(e.to_string()))?;
+
+    let action = ModAction {
+        target: None,
+        moderator: msg.author.clone(),
+        action_type: ModActionType::BanSync,
+        reason: None,
+        details: Some(format!(
+            "{} bans uploaded, {} bans withdrawn",
+            to_add.len(),
+            to_withdraw.len()
+        )),
+        guild: guild_id,
+        case_id: None,
+        old_value: None,
+        new_value: None,
+    };
+    log_mod_action(action, ctx).await;
+
     msg.channel_id
         .send_message(&ctx, |m| {
             m.embed(|e| {
-                let mut is_banned = false;
+                e.title("Ban Sync");
                 e.description("Finished syncing bans to the DSC Banlist");
+                e.field("Added", to_add.len(), true);
+                e.field("Withdrawn", to_withdraw.len(), true);
                 e.colour(Colour::DARK_GREEN);
                 e.footer(|f| {
                     f.text(format!("DSC Bot | Powered by Rusty Development"));
@@ -662,10 +1547,15 @@ async fn syncbans(ctx: &Context, msg: &Message) -> CommandResult {
 
     let guild = &ctx
         .http
-        .get_guild(*msg.guild_id.unwrap().as_u64())
+        .get_guild(*guild_id.as_u64())
         .await
         .unwrap();
-    info!("Synced bans from {}", &guild.name);
+    info!(
+        "Synced bans from {}: {} added, {} withdrawn",
+        &guild.name,
+        to_add.len(),
+        to_withdraw.len()
+    );
 
     debug!("Command finished");
     Ok(())
@@ -691,7 +1581,9 @@ async fn advise(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     };
     args.advance();
     let reason = args.rest();
-    let advise_channel = match &ctx.http.get_channel(646545388576178178).await {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let advise_channel = match &ctx.http.get_channel(crate::prelude::NOTIFY_CHANNEL).await {
         Ok(c) => c.id(),
         Err(err) => {
             error!("Error finding advisory channel: {:?}", err);
@@ -724,73 +1616,353 @@ async fn advise(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
         None => target_user.default_avatar_url(),
     };
 
-    match advise_channel
-        .send_message(&ctx, |m| {
-            m.embed(|e| {
-                e.title("New Advisory Sent");
-                e.fields(vec![
-                    ("User", target_user.name.clone(), false),
-                    ("Server", guild.name.clone(), false),
-                    ("Reason", String::from(reason), false),
-                ]);
-                e.color(Colour::ORANGE);
-                e.thumbnail(avatar_url);
+    let case_id = sqlx::query!(
+        "INSERT INTO advisories (userid, guild_id, moderator, reason) VALUES ($1, $2, $3, $4) RETURNING id",
+        target_user_id.as_u64().to_string(),
+        msg.guild_id.unwrap().as_u64().to_string(),
+        msg.author.id.as_u64().to_string(),
+        reason
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map_err(|e| CommandError(e.to_string()))?
+    .id;
+
+    match advise_channel
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("New Advisory Sent");
+                e.fields(vec![
+                    ("User", target_user.name.clone(), false),
+                    ("Server", guild.name.clone(), false),
+                    ("Reason", String::from(reason), false),
+                    ("Case", format!("#{}", case_id), false),
+                ]);
+                e.color(Colour::ORANGE);
+                e.thumbnail(avatar_url);
+                e.footer(|f| {
+                    f.text("DSC Bot | Powered by Rusty Development");
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await
+    {
+        Err(err) => {
+            error!("Error sending advisory message: {:?}", err);
+            return Err(CommandError(err.to_string()));
+        }
+        _ => (),
+    }
+
+    match msg
+        .channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Advisory Sent");
+                e.description(format!("Dispatched your advisory to DSC as case #{}.", case_id));
+                e.colour(Colour::DARK_GREEN);
+                e
+            });
+            m
+        })
+        .await
+    {
+        Err(err) => {
+            error!("Error responding to message: {:?}", err);
+            return Err(CommandError(err.to_string()));
+        }
+        _ => (),
+    }
+
+    let action = ModAction {
+        target: Some(target_user_id),
+        moderator: msg.author.clone(),
+        action_type: ModActionType::Advisory,
+        reason: Some(reason.to_string()),
+        details: None,
+        guild: msg.guild_id.unwrap(),
+        case_id: Some(case_id),
+        old_value: None,
+        new_value: None,
+    };
+    log_mod_action(action, ctx).await;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Modifies a current DSC ban"]
+#[usage("<Case Number> <Thing to modify> <What to modify it to>")]
+#[min_args(2)]
+#[checks(VibeOfficer)]
+#[only_in(guilds)]
+async fn modban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let case_id = &args.single::<i32>()?;
+    let modify_thing = &args.single::<String>().unwrap().to_lowercase();
+    let new_value = args.rest();
+
+    let current = match sqlx::query!("SELECT userid,guild_id,reason FROM dbans WHERE id = $1", *case_id)
+        .fetch_optional(pg_pool)
+        .await
+        .map_err(|e| CommandError(e.to_string()))?
+    {
+        Some(c) => c,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, format!("Could not find ban #{}.", case_id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let (old_value, logged_value) = if modify_thing == "reason" {
+        sqlx::query!(
+            "UPDATE dbans SET reason = $1 WHERE id = $2",
+            new_value, *case_id,
+        ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+        msg.channel_id
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Moderation");
+                    e.description(format!(
+                        "Successfully modified case {}!",
+                        case_id.to_string()
+                    ));
+                    e.field("Field", "Reason", true);
+                    e.field("New Value", new_value, true);
+                    e.colour(Colour::DARK_GREEN);
+                    e.footer(|f| {
+                        f.text(format!("Requested by {}", &msg.author.name));
+                        f
+                    });
+                    e
+                });
+                m
+            })
+            .await?;
+        (current.reason.clone(), new_value.to_string())
+    } else if modify_thing == "withdraw" {
+        sqlx::query!(
+            "UPDATE dbans SET is_withdrawn = 't' WHERE id = $1",
+            *case_id,
+        ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+        msg.channel_id
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Moderation");
+                    e.description(format!("Sucessfully withdrew case #{}", case_id));
+                    e.colour(Colour::DARK_GREEN);
+                    e.footer(|f| {
+                        f.text(format!("Requested by {}", &msg.author.name));
+                        f
+                    });
+                    e
+                });
+                m
+            })
+            .await?;
+        (String::from("Active"), String::from("Withdrawn"))
+    } else {
+        msg.channel_id
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Moderation");
+                    e.description("You can only modify a strike's reason or withdraw.");
+                    e.colour(Colour::RED);
+                    e.footer(|f| {
+                        f.text(format!("Requested by {}", &msg.author.name));
+                        f
+                    });
+                    e
+                });
+                m
+            })
+            .await?;
+        return Ok(());
+    };
+
+    // Log to the server that issued the ban, and here if that's somewhere else
+    let mut log_guilds = vec![msg.guild_id.unwrap()];
+    if let Ok(origin) = current.guild_id.parse::<u64>() {
+        if origin != *msg.guild_id.unwrap().as_u64() {
+            log_guilds.push(GuildId(origin));
+        }
+    }
+    for guild in log_guilds {
+        let action = ModAction {
+            target: current.userid.parse::<u64>().ok().map(UserId),
+            moderator: msg.author.clone(),
+            action_type: ModActionType::ModifyBan,
+            reason: None,
+            details: Some(format!("Changed {}", modify_thing)),
+            guild,
+            case_id: Some(*case_id),
+            old_value: Some(old_value.clone()),
+            new_value: Some(logged_value.clone()),
+        };
+        log_mod_action(action, ctx).await;
+    }
+
+    Ok(())
+}
+
+#[command]
+#[description = "Displays a list of strikes given to a user"]
+#[only_in(guilds)]
+#[min_args(1)]
+#[checks(VibeOfficer)]
+#[owner_privilege]
+async fn bans(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let target_user = args.parse::<UserId>().unwrap();
+    let bans = sqlx::query_as!(crate::models::DbanList, "SELECT reason,id,is_withdrawn FROM dbans WHERE userid = $1 ORDER BY id", target_user.as_u64().to_string()).fetch_all(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+
+    let mut result_vec: Vec<(String, String, bool)> = Vec::new();
+    for ban in bans {
+        if ban.is_withdrawn {
+            result_vec.push((format!("Case #{}", ban.id), format!("~~{}~~", ban.reason), false));
+        } else {
+            result_vec.push((format!("Case #{}", ban.id), ban.reason, false));
+        }
+    }
+
+    let target_user_name = target_user.to_user(&ctx.http).await.unwrap().name;
+
+    match msg
+        .channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                let mut title = String::from("Bans for ");
+                title.push_str(&target_user_name);
+                e.title(title);
+
+                e.fields(result_vec);
+
+                let mut footer = String::from("Requested by ");
+                footer.push_str(&msg.author.name);
                 e.footer(|f| {
-                    f.text("DSC Bot | Powered by Rusty Development");
+                    f.text(footer);
                     f
                 });
+
                 e
             });
+
             m
         })
         .await
     {
-        Err(err) => {
-            error!("Error sending advisory message: {:?}", err);
-            return Err(CommandError(err.to_string()));
-        }
-        _ => (),
+        Err(err) => error!("Error sending ban log: {:?}", err),
+        Ok(_msg) => (),
+    }
+
+    Ok(())
+}
+
+#[command]
+#[description = "Displays a list of advisories sent about a user"]
+#[only_in(guilds)]
+#[usage("<User>")]
+#[min_args(1)]
+#[checks(VibeOfficer)]
+#[owner_privilege]
+async fn advisories(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let target_user = args.parse::<UserId>()?;
+
+    let result = sqlx::query_as!(crate::models::Advisory, "SELECT id,guild_id,moderator,reason,created_at,is_withdrawn FROM advisories WHERE userid = $1 ORDER BY id", target_user.as_u64().to_string()).fetch_all(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
+
+    let mut result_vec: Vec<(String, String, bool)> = Vec::new();
+    for advisory in result.iter() {
+        let guild_name = match ctx.http.get_guild(advisory.guild_id.parse::<u64>()?).await {
+            Ok(g) => g.name,
+            Err(_) => advisory.guild_id.clone(),
+        };
+        let reason = if advisory.is_withdrawn {
+            format!("~~{}~~", advisory.reason)
+        } else {
+            advisory.reason.clone()
+        };
+        result_vec.push((
+            format!("Case #{}", advisory.id),
+            format!(
+                "{}\n{}, {}",
+                reason,
+                guild_name,
+                advisory.created_at.date().naive_utc()
+            ),
+            false,
+        ));
     }
 
+    let target_user_name = target_user.to_user(&ctx.http).await?.name;
+
     match msg
         .channel_id
-        .send_message(&ctx, |m| {
+        .send_message(&ctx.http, |m| {
             m.embed(|e| {
-                e.title("Advisory Sent");
-                e.description("Dispatched your advisory to DSC.");
-                e.colour(Colour::DARK_GREEN);
+                e.title(format!("Advisories for {}", target_user_name));
+                if result_vec.is_empty() {
+                    e.description("No advisories found.");
+                }
+                e.fields(result_vec);
+
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+
                 e
             });
+
             m
         })
         .await
     {
-        Err(err) => {
-            error!("Error responding to message: {:?}", err);
-            return Err(CommandError(err.to_string()));
-        }
-        _ => (),
+        Err(err) => error!("Error sending advisory log: {:?}", err),
+        Ok(_msg) => (),
     }
 
     Ok(())
 }
 
 #[command]
-#[description = "Modifies a current strike"]
+#[description = "Modifies a current advisory"]
 #[usage("<Case Number> <Thing to modify> <What to modify it to>")]
 #[min_args(2)]
 #[checks(VibeOfficer)]
 #[only_in(guilds)]
-async fn modban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+async fn modadvisory(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let bot_data = &ctx.data.read().await;
     let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
     let case_id = &args.single::<i32>()?;
     let modify_thing = &args.single::<String>().unwrap().to_lowercase();
     let new_value = args.rest();
 
-    if modify_thing == "reason" {
+    let current = match sqlx::query!("SELECT userid,guild_id,reason FROM advisories WHERE id = $1", *case_id)
+        .fetch_optional(pg_pool)
+        .await
+        .map_err(|e| CommandError(e.to_string()))?
+    {
+        Some(c) => c,
+        None => {
+            msg.channel_id
+                .say(&ctx.http, format!("Could not find advisory #{}.", case_id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let (old_value, logged_value) = if modify_thing == "reason" {
         sqlx::query!(
-            "UPDATE dbans SET reason = $1 WHERE id = $2",
+            "UPDATE advisories SET reason = $1 WHERE id = $2",
             new_value, *case_id,
         ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
         msg.channel_id
@@ -798,7 +1970,7 @@ async fn modban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
                 m.embed(|e| {
                     e.title("Moderation");
                     e.description(format!(
-                        "Successfully modified case {}!",
+                        "Successfully modified advisory {}!",
                         case_id.to_string()
                     ));
                     e.field("Field", "Reason", true);
@@ -813,16 +1985,17 @@ async fn modban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
                 m
             })
             .await?;
+        (current.reason.clone(), new_value.to_string())
     } else if modify_thing == "withdraw" {
         sqlx::query!(
-            "UPDATE dbans SET is_withdrawn = 't' WHERE id = $1",
+            "UPDATE advisories SET is_withdrawn = 't' WHERE id = $1",
             *case_id,
         ).execute(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
         msg.channel_id
             .send_message(&ctx, |m| {
                 m.embed(|e| {
                     e.title("Moderation");
-                    e.description(format!("Sucessfully withdrew case #{}", case_id));
+                    e.description(format!("Sucessfully withdrew advisory #{}", case_id));
                     e.colour(Colour::DARK_GREEN);
                     e.footer(|f| {
                         f.text(format!("Requested by {}", &msg.author.name));
@@ -833,12 +2006,13 @@ async fn modban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
                 m
             })
             .await?;
+        (String::from("Active"), String::from("Withdrawn"))
     } else {
         msg.channel_id
             .send_message(&ctx, |m| {
                 m.embed(|e| {
                     e.title("Moderation");
-                    e.description("You can only modify a strike's reason or withdraw.");
+                    e.description("You can only modify an advisory's reason or withdraw.");
                     e.colour(Colour::RED);
                     e.footer(|f| {
                         f.text(format!("Requested by {}", &msg.author.name));
@@ -849,159 +2023,150 @@ async fn modban(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
                 m
             })
             .await?;
+        return Ok(());
+    };
+
+    // Log to the server that issued the advisory, and here if that's somewhere else
+    let mut log_guilds = vec![msg.guild_id.unwrap()];
+    if let Ok(origin) = current.guild_id.parse::<u64>() {
+        if origin != *msg.guild_id.unwrap().as_u64() {
+            log_guilds.push(GuildId(origin));
+        }
+    }
+    for guild in log_guilds {
+        let action = ModAction {
+            target: current.userid.parse::<u64>().ok().map(UserId),
+            moderator: msg.author.clone(),
+            action_type: ModActionType::ModifyAdvisory,
+            reason: None,
+            details: Some(format!("Changed {}", modify_thing)),
+            guild,
+            case_id: Some(*case_id),
+            old_value: Some(old_value.clone()),
+            new_value: Some(logged_value.clone()),
+        };
+        log_mod_action(action, ctx).await;
     }
 
     Ok(())
 }
 
 #[command]
-#[description = "Displays a list of strikes given to a user"]
+#[description = "Puts the server into raid mode and alerts DSC. `--lockdown` stops @everyone from sending messages, `--kick` or `--quarantine` handles accounts that join during the raid, and a duration (e.g. `2h`) lifts raid mode automatically."]
+#[usage("[--lockdown] [--kick/--quarantine] [Duration]")]
 #[only_in(guilds)]
-#[min_args(1)]
-#[checks(VibeOfficer)]
-#[owner_privilege]
-async fn bans(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+#[checks(Moderator)]
+#[max_args(3)]
+async fn raid(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let bot_data = &ctx.data.read().await;
     let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
-    let target_user = args.parse::<UserId>().unwrap();
-    let mut bans: HashMap<StrikeLog, bool> = HashMap::new();
-    {
-        let result = sqlx::query_as!(crate::models::DbanList, "SELECT reason,id,is_withdrawn FROM dbans WHERE userid = $1", target_user.as_u64().to_string()).fetch_all(pg_pool).await.map_err(|e| CommandError(e.to_string()))?;
-        
+    let guild_id = msg.guild_id.unwrap();
 
-       for ban in result {
-            let reason = ban.reason;
-            let case_id = ban.id;
-            let is_withdrawn = ban.is_withdrawn;
-            let strike = StrikeLog {
-                user: target_user,
-                moderator: UserId(705876821232844910),
-                reason: reason,
-                case_id: case_id.to_string(),
-            };
-            bans.insert(strike, is_withdrawn);
+    let mut options = RaidOptions {
+        lockdown: false,
+        join_action: String::from("none"),
+        duration: None,
+    };
+    for arg in args.raw() {
+        match arg {
+            "--lockdown" => options.lockdown = true,
+            "--kick" => options.join_action = String::from("kick"),
+            "--quarantine" => options.join_action = String::from("quarantine"),
+            _ => match parse_duration(arg) {
+                Some(d) => options.duration = Some(d),
+                None => {
+                    msg.channel_id
+                        .say(&ctx.http, format!("Unknown raid option `{}`. Try using `help raid` to get usage.", arg))
+                        .await?;
+                    return Ok(());
+                }
+            },
         }
     }
-    let mut result_vec: Vec<(String, String, bool)> = Vec::new();
 
-    for (_i, (s, w)) in bans.iter().enumerate() {
-        if *w {
-            result_vec.push((
-                format!("Case #{}", s.case_id),
-                format!("~~{}~~", s.reason.clone()),
-                false,
-            ));
-        } else {
-            result_vec.push((format!("Case #{}", s.case_id), s.reason.clone(), false));
-        }
+    if get_active_raid(pg_pool, guild_id).await.map_err(CommandError)?.is_some() {
+        msg.channel_id
+            .say(&ctx.http, "The server is already in raid mode.")
+            .await?;
+        return Ok(());
     }
 
-    let target_user_name = target_user.to_user(&ctx.http).await.unwrap().name;
+    let ends_at = start_raid(ctx, pg_pool, guild_id, msg.author.id, &options)
+        .await
+        .map_err(CommandError)?;
 
-    match msg
+    let guild_name = match msg.guild(&ctx).await {
+        Some(a) => a.read().await.name.clone(),
+        None => return Err(CommandError("Could not find message guild".to_string())),
+    };
+    if let Err(e) = notify_raid(ctx, &guild_name, &format!("<@{}>", *msg.author.id.as_u64()), true).await {
+        return Err(CommandError(format!(
+            "Error alerting DSC to raid in {}: {:?}",
+            guild_name, e
+        )));
+    }
+    if let Err(e) = msg
         .channel_id
-        .send_message(&ctx.http, |m| {
+        .send_message(&ctx, |m| {
             m.embed(|e| {
-                let mut title = String::from("Bans for ");
-                title.push_str(&target_user_name);
-                e.title(title);
-
-                e.fields(result_vec);
-
-                let mut footer = String::from("Requested by ");
-                footer.push_str(&msg.author.name);
+                e.title("Raid Mode");
+                e.description("Successfully set raid mode on the server");
+                e.field("Lockdown", if options.lockdown { "Yes" } else { "No" }, true);
+                e.field("New Joins", &options.join_action, true);
+                match ends_at {
+                    Some(t) => e.field("Lifts At", format!("{}", t.format("%Y-%m-%d %H:%MZ")), true),
+                    None => e.field("Lifts At", "When `unraid` is run", true),
+                };
+                e.colour(Colour::RED);
                 e.footer(|f| {
-                    f.text(footer);
+                    f.text("DSC Bot | Powered by Rusty Development");
                     f
                 });
-
                 e
             });
-
             m
         })
         .await
     {
-        Err(err) => error!("Error sending ban log: {:?}", err),
-        Ok(_msg) => (),
+        return Err(CommandError(e.to_string()));
     }
-
     Ok(())
 }
 
 #[command]
-#[description = "Puts the server into raid mode and alerts DSC"]
+#[description = "Takes the server out of raid mode and alerts DSC"]
 #[only_in(guilds)]
 #[checks(Moderator)]
 #[num_args(0)]
-async fn raid(ctx: &Context, msg: &Message) -> CommandResult {
-    let http_cache = &ctx.http;
-    let guild_arc = match msg.guild(&ctx).await {
-        Some(a) => a,
-        None => return Err(CommandError("Could not find message guild".to_string())),
-    };
-    let guild = guild_arc.read().await;
-    let mut guild_channels = match guild.channels(http_cache).await {
-        Ok(h) => h,
-        Err(e) => return Err(CommandError(e.to_string())),
-    };
+async fn unraid(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let guild_id = msg.guild_id.unwrap();
 
-    for (id, gc) in guild_channels.iter_mut() {
-        match gc.kind {
-            ChannelType::Text => {
-                if let Err(e) = gc
-                    .edit(http_cache, |e| {
-                        e.slow_mode_rate(21600);
-                        e
-                    })
-                    .await
-                {
-                    error!(
-                        "Error in raid: Could not set slowmode on channel {}: {:?}",
-                        id.as_u64().to_string(),
-                        e
-                    );
-                }
-            }
-            _ => continue,
-        }
+    if !end_raid(ctx, pg_pool, guild_id, msg.author.id).await.map_err(CommandError)? {
+        msg.channel_id
+            .say(&ctx.http, "The server is not in raid mode.")
+            .await?;
+        return Ok(());
     }
-    if let Ok(c) = http_cache.get_channel(crate::prelude::NOTIFY_CHANNEL).await {
-        if let Err(e) = c
-            .id()
-            .send_message(&ctx, |m| {
-                m.content("@everyone");
-                m.embed(|e| {
-                    e.title("RAID IN PROGRESS");
-                    e.fields(vec![
-                        ("Server", guild.name.clone(), true),
-                        ("Moderator", format!("<@{}>", *msg.author.id.as_u64()), true),
-                    ]);
-                    e.footer(|f| {
-                        f.text("DSC Bot | Powered by Rusty Development");
-                        f
-                    });
-                    e.colour(Colour::RED);
-                    e
-                });
-                m
-            })
-            .await
-        {
-            return Err(CommandError(format!(
-                "Error alerting DSC to raid in {}: {:?}",
-                guild.name.clone(),
-                e.to_string()
-            )));
-        }
+
+    let guild_name = match msg.guild(&ctx).await {
+        Some(a) => a.read().await.name.clone(),
+        None => return Err(CommandError("Could not find message guild".to_string())),
+    };
+    if let Err(e) = notify_raid(ctx, &guild_name, &format!("<@{}>", *msg.author.id.as_u64()), false).await {
+        return Err(CommandError(format!(
+            "Error alerting DSC to unraid in {}: {:?}",
+            guild_name, e
+        )));
     }
     if let Err(e) = msg
         .channel_id
         .send_message(&ctx, |m| {
             m.embed(|e| {
                 e.title("Raid Mode");
-                e.description("Successfully set raid mode on the server");
-                e.colour(Colour::RED);
+                e.description("Successfully removed raid mode on the server");
+                e.colour(Colour::BLUE);
                 e.footer(|f| {
                     f.text("DSC Bot | Powered by Rusty Development");
                     f
@@ -1017,89 +2182,285 @@ async fn raid(ctx: &Context, msg: &Message) -> CommandResult {
     Ok(())
 }
 
+const MODLOG_PAGE_SIZE: usize = 10;
+
+/// Characters shared between the entries on a modlog page, leaving room for the title and
+/// footer under Discord's 6000 character embed limit
+const MODLOG_EMBED_BUDGET: usize = 5000;
+
+/// Search options given to `modlog`
+struct ModlogFilter {
+    target: Option<String>,
+    moderator: Option<String>,
+    action_type: Option<String>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+async fn count_modlog_entries(
+    pg_pool: &PgPool,
+    guild_id: GuildId,
+    filter: &ModlogFilter,
+) -> Result<i64, CommandError> {
+    Ok(sqlx::query!(
+        "SELECT COUNT(*) AS count FROM mod_actions WHERE guild_id = $1 AND ($2::TEXT IS NULL OR target = $2) AND ($3::TEXT IS NULL OR moderator = $3) AND ($4::TEXT IS NULL OR action_type = $4) AND ($5::TIMESTAMPTZ IS NULL OR created_at >= $5) AND ($6::TIMESTAMPTZ IS NULL OR created_at < $6)",
+        guild_id.as_u64().to_string(),
+        filter.target,
+        filter.moderator,
+        filter.action_type,
+        filter.since,
+        filter.until
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map_err(|e| CommandError(e.to_string()))?
+    .count
+    .unwrap_or(0))
+}
+
+/// Fetches one page of matching entries, newest first, formatted as embed fields.
+async fn fetch_modlog_page(
+    pg_pool: &PgPool,
+    guild_id: GuildId,
+    filter: &ModlogFilter,
+    page: usize,
+) -> Result<Vec<(String, String, bool)>, CommandError> {
+    let entries = sqlx::query_as!(
+        ModActionLog,
+        "SELECT id,target,moderator,action_type,reason,details,case_id,old_value,new_value,created_at FROM mod_actions WHERE guild_id = $1 AND ($2::TEXT IS NULL OR target = $2) AND ($3::TEXT IS NULL OR moderator = $3) AND ($4::TEXT IS NULL OR action_type = $4) AND ($5::TIMESTAMPTZ IS NULL OR created_at >= $5) AND ($6::TIMESTAMPTZ IS NULL OR created_at < $6) ORDER BY id DESC LIMIT $7 OFFSET $8",
+        guild_id.as_u64().to_string(),
+        filter.target,
+        filter.moderator,
+        filter.action_type,
+        filter.since,
+        filter.until,
+        MODLOG_PAGE_SIZE as i64,
+        (page * MODLOG_PAGE_SIZE) as i64
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|e| CommandError(e.to_string()))?;
+
+    let limit = (MODLOG_EMBED_BUDGET / entries.len().max(1)).min(EMBED_FIELD_LIMIT);
+    Ok(entries.iter().map(|e| format_modlog_entry(e, limit)).collect())
+}
+
 #[command]
-#[description = "Takes the server out of raid mode and alerts DSC"]
+#[description = "Searches this server's moderation log. Dates are given as YYYY-MM-DD; use the arrow reactions to page through results."]
 #[only_in(guilds)]
+#[usage("[--user <User>] [--moderator <User>] [--type <Action Type>] [--since <Date>] [--until <Date>]")]
 #[checks(Moderator)]
-#[num_args(0)]
-async fn unraid(ctx: &Context, msg: &Message) -> CommandResult {
-    let http_cache = &ctx.http;
-    let guild_arc = match msg.guild(&ctx).await {
-        Some(a) => a,
-        None => return Err(CommandError("Could not find message guild".to_string())),
-    };
-    let guild = guild_arc.read().await;
-    let mut guild_channels = match guild.channels(http_cache).await {
-        Ok(h) => h,
-        Err(e) => return Err(CommandError(e.to_string())),
-    };
-
-    for (id, gc) in guild_channels.iter_mut() {
-        match gc.kind {
-            ChannelType::Text => {
-                if let Err(e) = gc
-                    .edit(http_cache, |e| {
-                        e.slow_mode_rate(0);
-                        e
-                    })
-                    .await
-                {
-                    error!(
-                        "Error in unraid: Could not remove slowmode on channel {}: {:?}",
-                        id.as_u64().to_string(),
-                        e
-                    );
+async fn modlog(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let guild_id = msg.guild_id.unwrap();
+
+    let mut target: Option<String> = None;
+    let mut moderator: Option<String> = None;
+    let mut action_type: Option<String> = None;
+    let mut since: Option<DateTime<Utc>> = None;
+    let mut until: Option<DateTime<Utc>> = None;
+    while !args.is_empty() {
+        let flag = args.single::<String>()?;
+        let value = match args.single::<String>() {
+            Ok(v) => v,
+            Err(_) => {
+                msg.channel_id
+                    .say(&ctx.http, format!("Missing value for {}", flag))
+                    .await?;
+                return Ok(());
+            }
+        };
+        match flag.as_str() {
+            "--user" => target = Some(value.parse::<UserId>()?.as_u64().to_string()),
+            "--moderator" => moderator = Some(value.parse::<UserId>()?.as_u64().to_string()),
+            "--type" => match ModActionType::from_key(&value.to_lowercase()) {
+                Some(t) => action_type = Some(t.key().to_string()),
+                None => {
+                    msg.channel_id
+                        .say(&ctx.http, format!("Unknown action type: {}", value))
+                        .await?;
+                    return Ok(());
+                }
+            },
+            "--since" | "--until" => {
+                let date = match NaiveDate::parse_from_str(&value, "%Y-%m-%d") {
+                    Ok(d) => DateTime::<Utc>::from_utc(d.and_hms(0, 0, 0), Utc),
+                    Err(_) => {
+                        msg.channel_id
+                            .say(&ctx.http, format!("Invalid date: {}. Use YYYY-MM-DD.", value))
+                            .await?;
+                        return Ok(());
+                    }
+                };
+                if flag == "--since" {
+                    since = Some(date);
+                } else {
+                    // Include the whole of the end date
+                    until = Some(date + chrono::Duration::days(1));
                 }
             }
-            _ => continue,
-        }
-    }
-    if let Ok(c) = http_cache.get_channel(crate::prelude::NOTIFY_CHANNEL).await {
-        if let Err(e) = c
-            .id()
-            .send_message(&ctx, |m| {
-                m.embed(|e| {
-                    e.title("Raid Mode Lifted");
-                    e.fields(vec![
-                        ("Server", guild.name.clone(), true),
-                        ("Moderator", format!("<@{}>", *msg.author.id.as_u64()), true),
-                    ]);
-                    e.footer(|f| {
-                        f.text("DSC Bot | Powered by Rusty Development");
-                        f
-                    });
-                    e.colour(Colour::BLUE);
-                    e
-                });
-                m
-            })
-            .await
-        {
-            return Err(CommandError(format!(
-                "Error alerting DSC to unraid in {}: {:?}",
-                guild.name.clone(),
-                e.to_string()
-            )));
+            _ => {
+                msg.channel_id
+                    .say(&ctx.http, format!("Unknown option: {}", flag))
+                    .await?;
+                return Ok(());
+            }
         }
     }
-    if let Err(e) = msg
+
+    let filter = ModlogFilter {
+        target,
+        moderator,
+        action_type,
+        since,
+        until,
+    };
+    let total = count_modlog_entries(pg_pool, guild_id, &filter).await?;
+    let page_count = ((total as usize + MODLOG_PAGE_SIZE - 1) / MODLOG_PAGE_SIZE).max(1);
+    let mut page = 0;
+    let fields = fetch_modlog_page(pg_pool, guild_id, &filter, page).await?;
+
+    let mut log_message = msg
         .channel_id
-        .send_message(&ctx, |m| {
+        .send_message(&ctx.http, |m| {
             m.embed(|e| {
-                e.title("Raid Mode");
-                e.description("Successfully removed raid mode on the server");
-                e.colour(Colour::BLUE);
+                e.title("Moderation Log");
+                if fields.is_empty() {
+                    e.description("No entries found.");
+                } else {
+                    e.description(format!("{} entries found", total));
+                    e.fields(fields);
+                }
                 e.footer(|f| {
-                    f.text("DSC Bot | Powered by Rusty Development");
+                    f.text(format!(
+                        "Page {} of {} | Requested by {}",
+                        page + 1,
+                        page_count,
+                        &msg.author.name
+                    ));
                     f
                 });
                 e
             });
             m
         })
+        .await?;
+
+    if page_count == 1 {
+        return Ok(());
+    }
+    log_message.react(&ctx.http, '◀').await?;
+    log_message.react(&ctx.http, '▶').await?;
+
+    while let Some(action) = log_message
+        .await_reaction(&ctx)
+        .author_id(msg.author.id)
+        .timeout(Duration::from_secs(120))
         .await
     {
-        return Err(CommandError(e.to_string()));
+        let reaction = action.as_inner_ref();
+        let new_page = match &reaction.emoji {
+            ReactionType::Unicode(e) if e == "◀" && page > 0 => page - 1,
+            ReactionType::Unicode(e) if e == "▶" && page + 1 < page_count => page + 1,
+            _ => continue,
+        };
+        // Needs Manage Messages, so paging still works without it
+        let _ = reaction.delete(&ctx).await;
+        page = new_page;
+        let fields = fetch_modlog_page(pg_pool, guild_id, &filter, page).await?;
+
+        log_message
+            .edit(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Moderation Log");
+                    e.description(format!("{} entries found", total));
+                    e.fields(fields);
+                    e.footer(|f| {
+                        f.text(format!(
+                            "Page {} of {} | Requested by {}",
+                            page + 1,
+                            page_count,
+                            &msg.author.name
+                        ));
+                        f
+                    });
+                    e
+                });
+                m
+            })
+            .await?;
     }
+
     Ok(())
 }
+
+/// Formats an entry as an embed field, cutting its free text down so the value is at most
+/// `limit` characters.
+fn format_modlog_entry(entry: &ModActionLog, limit: usize) -> (String, String, bool) {
+    let type_name = match ModActionType::from_key(&entry.action_type) {
+        Some(t) => t.name(),
+        None => entry.action_type.as_str(),
+    };
+
+    let mut header = String::new();
+    if let Some(t) = &entry.target {
+        header.push_str(&format!("User: <@{}>\n", t));
+    }
+    header.push_str(&format!("Moderator: <@{}>\n", entry.moderator));
+    if let Some(c) = entry.case_id {
+        header.push_str(&format!("Case #{}\n", c));
+    }
+
+    let mut text = String::new();
+    if let Some(r) = &entry.reason {
+        text.push_str(&format!("Reason: {}\n", truncate_field(r)));
+    }
+    if let Some(d) = &entry.details {
+        text.push_str(&format!("Details: {}\n", truncate_field(d)));
+    }
+    match (&entry.old_value, &entry.new_value) {
+        (Some(o), Some(n)) => text.push_str(&format!("{} → {}\n", truncate_field(o), truncate_field(n))),
+        (Some(o), None) => text.push_str(&format!("Old Value: {}\n", truncate_field(o))),
+        (None, Some(n)) => text.push_str(&format!("New Value: {}\n", truncate_field(n))),
+        (None, None) => (),
+    }
+    let date = entry.created_at.format("%Y-%m-%d %H:%M UTC").to_string();
+
+    let room = limit.saturating_sub(header.chars().count() + date.chars().count() + 1);
+    let mut text = truncate_chars(&text, room);
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+
+    (
+        format!("#{} {}", entry.id, type_name),
+        format!("{}{}{}", header, text, date),
+        false,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modlog_entries_fit_their_share_of_the_page() {
+        let entry = ModActionLog {
+            id: 1,
+            target: Some(String::from("1")),
+            moderator: String::from("2"),
+            action_type: String::from("bad_word_delete"),
+            reason: Some(String::from("Found a banned word")),
+            details: None,
+            case_id: None,
+            old_value: Some("a".repeat(2000)),
+            new_value: None,
+            created_at: Utc::now(),
+        };
+        let limit = MODLOG_EMBED_BUDGET / MODLOG_PAGE_SIZE;
+        let (_, value, _) = format_modlog_entry(&entry, limit);
+        assert!(value.chars().count() <= limit);
+        assert!(value.ends_with(" UTC"));
+    }
+}
@@ -4,6 +4,8 @@
  */
 
 use crate::util::data::get_pickle_database;
+use crate::util::import::*;
+use crate::util::wordfilter::invalidate_filter;
 use serenity::framework::standard::{macros::command, CommandResult};
 use serenity::{model::channel::Message, prelude::*};
 
@@ -72,3 +74,45 @@ async fn initcache(ctx: &Context, msg: &Message) -> CommandResult {
 
     Ok(())
 }
+
+#[command]
+#[description = "Imports the old files under ./data into the database, keeping anything already stored there"]
+#[owners_only]
+async fn importdata(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let guilds = guild_data_dirs().map_err(CommandError)?.len();
+    let settings = import_settings(pg_pool).await.map_err(CommandError)?;
+    let verified = import_verified_roles(pg_pool).await.map_err(CommandError)?;
+    let ages = import_age_groups(pg_pool).await.map_err(CommandError)?;
+    let filter = import_word_filter(pg_pool).await.map_err(CommandError)?;
+    let allowed = import_allowed_words(pg_pool).await.map_err(CommandError)?;
+    let exemptions = import_exemptions(pg_pool).await.map_err(CommandError)?;
+    invalidate_filter(ctx, None).await;
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Data Import");
+                e.description(format!("Imported data from {} servers", guilds));
+                e.fields(vec![
+                    ("Settings", settings, true),
+                    ("Verified roles", verified, true),
+                    ("Age groups", ages, true),
+                    ("Word filter entries", filter, true),
+                    ("Allowed words", allowed, true),
+                    ("Exemptions", exemptions, true),
+                ]);
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await?;
+    warn!("{} imported the data of {} servers", &msg.author.name, guilds);
+
+    Ok(())
+}
@@ -5,56 +5,156 @@
 
 use crate::checks::*;
 use crate::prelude::*;
-use crate::util::data::{get_pickle_database, init_guild_settings};
+use crate::util::moderation::{log_mod_action, ModAction, ModActionType};
+use crate::util::settings::{get_settings_registry, init_guild_settings, lookup_setting, GuildSettings, Setting};
 use serenity::framework::standard::{macros::command, Args, CommandResult};
-use serenity::model::id::{ChannelId, RoleId};
 use serenity::utils::Colour;
 use serenity::{model::channel::Message, prelude::*};
 
+/// Settings shown per embed by `serversettings`, to stay under Discord's 25 field limit
+const SETTINGS_PER_PAGE: usize = 20;
+
 #[command]
-#[description = "Manage server settings"]
+#[description = "Lists every server setting with its current value. Use `serversettings get` for details on one setting and `serversettings set` to change it."]
 #[checks(Moderator)]
 #[sub_commands(get, set)]
 #[only_in(guilds)]
 async fn serversettings(ctx: &Context, msg: &Message) -> CommandResult {
-    if let Err(err) = msg.channel_id.send_message(&ctx, |m| {
-        m.embed(|e| {
-            e.title("Server Settings Help");
-            e.description("Changes and views server settings for the current server\n\nUse a value from the table below to change or view a setting");
-            e.fields(vec![
-                ("Usage", "serversettings set/get <setting> (value)", false),
-                ("Banned User Announcements", "modlogs_channel", true)
-            ]);
-            e.footer(|f| {
-                f.text(format!("Requested by {}", &msg.author.name));
-                f
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let db = GuildSettings::load(pg_pool, msg.guild_id.unwrap())
+        .await
+        .map_err(CommandError)?;
+    let registry = get_settings_registry(pg_pool).await.map_err(CommandError)?;
+    let pages: Vec<&[Setting]> = registry.chunks(SETTINGS_PER_PAGE).collect();
+
+    for (i, page) in pages.iter().enumerate() {
+        let fields: Vec<(String, String, bool)> = page
+            .iter()
+            .map(|setting| {
+                (
+                    format!("{} ({})", setting.name, setting.setting_type.name()),
+                    format!("{}\n*{}*", setting.display(&setting.get(&db)), setting.description),
+                    true,
+                )
+            })
+            .collect();
+
+        if let Err(err) = msg.channel_id.send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Server Settings");
+                if i == 0 {
+                    e.description("Changes and views server settings for the current server\n\nUsage: `serversettings set <setting> <value>`, `serversettings get <setting>`, `resetsettings [setting]`");
+                }
+                e.fields(fields);
+                e.footer(|f| {
+                    f.text(format!("Page {} of {} | Requested by {}", i + 1, pages.len(), &msg.author.name));
+                    f
+                });
+                e
             });
-            e
-        });
-        m
-    }).await {
-        error!("Error sending server settings help: {:?}", err);
+            m
+        }).await {
+            error!("Error sending server settings help: {:?}", err);
+        }
     }
 
     Ok(())
 }
 
+async fn send_unknown_setting(ctx: &Context, msg: &Message, setting_name: &str) -> CommandResult {
+    msg.channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Server Settings");
+                e.description(format!(
+                    "Setting {} does not exist. Run `serversettings` to see every setting.",
+                    setting_name
+                ));
+                e.colour(Colour::RED);
+
+                e
+            });
+
+            m
+        })
+        .await?;
+
+    Ok(())
+}
+
 #[command]
-#[description = "Gets current value of a setting"]
+#[description = "Gets the current value of a setting, along with its type and default"]
+#[usage("<Setting>")]
 #[checks(Moderator)]
+#[only_in(guilds)]
 #[num_args(1)]
 async fn get(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let db = get_pickle_database(&msg.guild_id.unwrap().as_u64(), "settings.db");
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let db = GuildSettings::load(pg_pool, msg.guild_id.unwrap())
+        .await
+        .map_err(CommandError)?;
+    let setting = match lookup_setting(pg_pool, args.rest().trim())
+        .await
+        .map_err(CommandError)?
+    {
+        Some(s) => s,
+        None => return send_unknown_setting(ctx, msg, args.rest().trim()).await,
+    };
+
+    msg.channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Server Settings");
+                e.description(&setting.description);
+                e.field("Setting", &setting.name, true);
+                e.field("Value", setting.display(&setting.get(&db)), true);
+                e.field("Default", setting.display(&setting.default_value()), true);
+                e.field("Type", setting.setting_type.name(), true);
+                e.field("Accepts", setting.setting_type.expected(), true);
+                e.colour(Colour::DARK_GREEN);
+
+                e
+            });
+
+            m
+        })
+        .await?;
+
+    Ok(())
+}
 
-    match db.get::<String>(&args.rest()) {
-        Some(s) => {
+#[command]
+#[description = "Sets a setting. Run `serversettings` to see every setting."]
+#[usage("<Setting> <Value>")]
+#[checks(Moderator)]
+#[only_in(guilds)]
+#[min_args(2)]
+async fn set(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let mut db = GuildSettings::load(pg_pool, msg.guild_id.unwrap())
+        .await
+        .map_err(CommandError)?;
+    let setting_name = args.single::<String>()?;
+    let setting = match lookup_setting(pg_pool, &setting_name)
+        .await
+        .map_err(CommandError)?
+    {
+        Some(s) => s,
+        None => return send_unknown_setting(ctx, msg, &setting_name).await,
+    };
+
+    let new_value = match setting.parse(args.rest()) {
+        Ok(v) => v,
+        Err(err) => {
             msg.channel_id
                 .send_message(&ctx, |m| {
                     m.embed(|e| {
                         e.title("Server Settings");
-                        e.field("Setting", &args.rest(), true);
-                        e.field("Value", s, true);
-                        e.colour(Colour::DARK_GREEN);
+                        e.description(err);
+                        e.colour(Colour::RED);
 
                         e
                     });
@@ -62,114 +162,117 @@ async fn get(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
                     m
                 })
                 .await?;
+            return Ok(());
         }
-        None => {
-            msg.channel_id.send_message(&ctx, |m| {
-                m.embed(|e| {
-                    e.title("Server Settings");
-                    let mut description = String::from("");
-                    description.push_str("Could not find that setting. Check your spelling and try again\n");
-                    description.push_str("\nIf you believe that this setting *should* exist, try running `cinitsettings` to get the default server settings initialized.");
-                    e.description(description);
-                    e.colour(Colour::RED);
-
-                    e
-                });
-
-                m
-            }).await?;
-        }
-    }
+    };
 
-    Ok(())
+    let old_value = setting.get(&db);
+    db.set(pg_pool, &setting.name, &new_value)
+        .await
+        .map_err(CommandError)?;
+    send_setting_changed(
+        ctx,
+        msg,
+        &setting.name,
+        &setting.display(&new_value),
+        &setting.display(&old_value),
+    )
+    .await
 }
 
-#[command]
-#[description = "Sets a setting"]
-#[usage("<Setting> <Value>")]
-#[min_args(2)]
-async fn set(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let mut settings = get_pickle_database(&msg.guild_id.unwrap().as_u64(), "settings.db");
-    let setting_name = args.current().unwrap();
-    let mut arg_value = args.clone();
-    let setting_value = if setting_name.to_lowercase().contains("role") {
-        arg_value
-            .advance()
-            .rest()
-            .parse::<RoleId>()
-            .unwrap()
-            .as_u64()
-            .clone()
-    } else if setting_name.to_lowercase().contains("channel") {
-        let channel = &arg_value.advance().rest().parse::<ChannelId>().unwrap();
-        channel.as_u64().clone()
-    } else {
-        0u64
+async fn send_setting_changed(
+    ctx: &Context,
+    msg: &Message,
+    setting_name: &str,
+    new_value: &str,
+    old_value: &str,
+) -> CommandResult {
+    let action = ModAction {
+        target: None,
+        moderator: msg.author.clone(),
+        action_type: ModActionType::SettingChange,
+        reason: None,
+        details: Some(setting_name.to_string()),
+        guild: msg.guild_id.unwrap(),
+        case_id: None,
+        old_value: Some(old_value.to_string()),
+        new_value: Some(new_value.to_string()),
     };
+    log_mod_action(action, ctx).await;
 
-    if setting_value == 0 {
-        msg.channel_id
-            .send_message(&ctx, |m| {
-                m.embed(|e| {
-                    e.title("Server Settings");
-                    e.description(format!("Invalid setting value."));
-
-                    e
-                });
-
-                m
-            })
-            .await?;
-    }
-
-    if let None = settings.get::<u64>(&setting_name) {
-        msg.channel_id
-            .send_message(&ctx, |m| {
-                m.embed(|e| {
-                    e.title("Server Settings");
-                    e.description(format!(
-                        "Setting {} does not exist. Refer to command help",
-                        setting_name
-                    ));
-                    e.colour(Colour::RED);
-                    e
-                });
-                m
-            })
-            .await?;
-    } else {
-        let old_value = settings.get::<u64>(&setting_name).unwrap();
-        settings.set(setting_name, &setting_value)?;
-        msg.channel_id
-            .send_message(&ctx, |m| {
-                m.embed(|e| {
-                    e.title("Server Settings");
-                    e.description("Successfully changed setting");
-                    e.field("Setting", setting_name, true);
-                    e.field("New Value", setting_value, true);
-                    e.field("Old Value", old_value, false);
-                    e.colour(Colour::DARK_GREEN);
-                    e.footer(|f| {
-                        f.text(format!("Requested by {}", &msg.author.name));
-                        f
-                    });
-                    e
+    msg.channel_id
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Server Settings");
+                e.description("Successfully changed setting");
+                e.field("Setting", setting_name, true);
+                e.field("New Value", new_value, true);
+                e.field("Old Value", old_value, false);
+                e.colour(Colour::DARK_GREEN);
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
                 });
-                m
-            })
-            .await?;
-    }
+                e
+            });
+            m
+        })
+        .await?;
 
     Ok(())
 }
 
 #[command]
-#[description = "Resets server settings"]
+#[description = "Resets one server setting, or every setting if none is given, to its default"]
+#[usage("[Setting]")]
 #[checks(Moderator)]
-async fn resetsettings(ctx: &Context, msg: &Message) -> CommandResult {
-    let mut db = get_pickle_database(&msg.guild_id.unwrap().as_u64(), "settings.db");
+#[only_in(guilds)]
+async fn resetsettings(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let bot_data = &ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let mut db = GuildSettings::load(pg_pool, msg.guild_id.unwrap())
+        .await
+        .map_err(CommandError)?;
+
+    if !args.is_empty() {
+        let setting = match lookup_setting(pg_pool, args.rest().trim())
+            .await
+            .map_err(CommandError)?
+        {
+            Some(s) => s,
+            None => return send_unknown_setting(ctx, msg, args.rest().trim()).await,
+        };
+        let old_value = setting.get(&db);
+        let new_value = setting.default_value();
+        db.set(pg_pool, &setting.name, &new_value)
+            .await
+            .map_err(CommandError)?;
+        return send_setting_changed(
+            ctx,
+            msg,
+            &setting.name,
+            &setting.display(&new_value),
+            &setting.display(&old_value),
+        )
+        .await;
+    }
 
-    init_guild_settings(&mut db);
+    init_guild_settings(pg_pool, msg.guild_id.unwrap())
+        .await
+        .map_err(CommandError)?;
+
+    let action = ModAction {
+        target: None,
+        moderator: msg.author.clone(),
+        action_type: ModActionType::SettingsReset,
+        reason: None,
+        details: None,
+        guild: msg.guild_id.unwrap(),
+        case_id: None,
+        old_value: None,
+        new_value: None,
+    };
+    log_mod_action(action, ctx).await;
 
     msg.channel_id
         .send_message(&ctx, |m| {
@@ -2,30 +2,55 @@
  *   Copyright (c) 2020 Owen Salter <owen@devosmium.xyz>
  *   All rights reserved.
  */
+use crate::checks::*;
+use crate::models::{VerificationRequest, VerificationType};
 use crate::prelude::*;
+use crate::util::moderation::ModActionType;
+use crate::util::truncate_field;
+use crate::util::verification::{
+    approve_request, award_name, close_request, find_verification_type, get_pending_requests,
+    get_request, get_verification_types, log_verification, match_deny_emoji, revoke_verified,
+    sync_award_roles, DenyReason, CLOSE_EMOJI,
+};
+
+/// Requests shown by `vqueue`, to stay under Discord's 25 field limit
+const VQUEUE_LIMIT: usize = 20;
+
+/// Characters the `vqueue` fields can use, leaving room for the title and description under
+/// Discord's 6000 character embed limit
+const VQUEUE_EMBED_BUDGET: usize = 5500;
+
+/// Attachments linked per request in `vqueue`, keeping the most recent
+const VQUEUE_PROOF_LINKS: usize = 3;
+
+/// Active verification types allowed, so the type reactions plus close and the two deny
+/// reactions stay under Discord's 20 reactions per message
+const MAX_VERIFICATION_TYPES: usize = 17;
 
 #[command]
 #[description = "Allows users to set their age group as over/under 18"]
 #[usage("<over/under>")]
 #[num_args(1)]
 async fn age(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
-    let mut age_db = get_global_pickle_database("age.db");
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
     let overunder = args.current().unwrap();
-    if overunder == "over" {
-        match age_db.set(&msg.author.id.as_u64().to_string(), &"Over") {
-            Err(err) => {
-                error!("Error setting {}'s age: {:?}", &msg.author.name, err);
-                return Err(CommandError(err.to_string()));
-            }
-            _ => (),
-        }
-    } else if overunder == "under" {
-        match age_db.set(&msg.author.id.as_u64().to_string(), &"Under") {
-            Err(err) => {
-                error!("Error setting {}'s age: {:?}", &msg.author.name, err);
-                return Err(CommandError(err.to_string()));
-            }
-            _ => (),
+    let age_group = match overunder {
+        "over" => Some("Over"),
+        "under" => Some("Under"),
+        _ => None,
+    };
+    if let Some(age_group) = age_group {
+        if let Err(err) = sqlx::query!(
+            "INSERT INTO age_groups (userid, age_group) VALUES ($1, $2) ON CONFLICT (userid) DO UPDATE SET age_group = EXCLUDED.age_group",
+            msg.author.id.as_u64().to_string(),
+            age_group
+        )
+        .execute(pg_pool)
+        .await
+        {
+            error!("Error setting {}'s age: {:?}", &msg.author.name, err);
+            return Err(CommandError(err.to_string()));
         }
     } else {
         match msg.channel_id.send_message(&ctx, |m| {
@@ -90,18 +115,18 @@ async fn age(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 #[command]
 #[description = "Gives instructions on how to verify your Awards and Advancements"]
 async fn verify(ctx: &Context, msg: &Message) -> CommandResult {
+    let types = {
+        let bot_data = ctx.data.read().await;
+        let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+        get_verification_types(pg_pool, false)
+            .await
+            .map_err(CommandError)?
+    };
     if let Err(err) = msg.channel_id.send_message(&ctx, |m| {
         m.embed(|e| {
             e.title("Verification");
-            e.description("In order to verify your roles, please send a direct message to the bot, and attach an image of your proof.\n\nScoutbook screenshots that clearly show a completed award are valid proof for BSA awards.");
-            e.fields(vec![
-                ("Eagle Scout", "Patch, Card, or Certificate", true),
-                ("Summit/Silver", "Patch, Card, or Certificate", true),
-                ("Camp Staff", "Name tag or shirt", true),
-                ("YPT", "Certificate. PDF is acceptable for this verification", true),
-                ("OA Honor", "Sash or membership card", true),
-                ("Quartermaster", "Medal, Patch, Card, and Certificate", true)
-            ]);
+            e.description("In order to verify your roles, please send a direct message to the bot with the name of the award, and attach an image of your proof.\n\nScoutbook screenshots that clearly show a completed award are valid proof for BSA awards.");
+            e.fields(types.iter().map(|t| (&t.name, &t.proof, true)));
             e.footer(|f| {
                 f.text("DSC Bot | Powered by Rusty Development");
                 f
@@ -118,3 +143,488 @@ async fn verify(ctx: &Context, msg: &Message) -> CommandResult {
 
     Ok(())
 }
+
+#[command]
+#[description = "Lists the verification requests waiting for review"]
+#[checks(VibeOfficer)]
+async fn vqueue(ctx: &Context, msg: &Message) -> CommandResult {
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let pending = get_pending_requests(pg_pool).await.map_err(CommandError)?;
+    let types = get_verification_types(pg_pool, true)
+        .await
+        .map_err(CommandError)?;
+
+    let mut fields = Vec::new();
+    let mut embed_length = 0;
+    for request in pending.iter().take(VQUEUE_LIMIT) {
+        let award = match &request.requested_award {
+            Some(a) => award_name(&types, a),
+            None => String::from("Not specified"),
+        };
+        // Resubmissions add attachments, so only the latest are linked
+        let attachments: Vec<&str> = request.attachments.lines().collect();
+        let older = attachments.len().saturating_sub(VQUEUE_PROOF_LINKS);
+        let mut proof = attachments
+            .iter()
+            .enumerate()
+            .skip(older)
+            .map(|(i, url)| format!("[{}]({})", i + 1, url))
+            .collect::<Vec<String>>()
+            .join(" ");
+        if older > 0 {
+            proof.push_str(&format!(" (+{} older)", older));
+        }
+        let mut title = format!("Request #{}", request.id);
+        if request.resubmissions > 0 {
+            title.push_str(" (resubmitted)");
+        }
+        let value = truncate_field(&format!(
+            "<@{}> - {}\nSubmitted {}\nProof: {}",
+            request.requester,
+            award,
+            request.created_at.date().naive_utc(),
+            proof
+        ));
+
+        embed_length += title.chars().count() + value.chars().count();
+        if embed_length > VQUEUE_EMBED_BUDGET {
+            break;
+        }
+        fields.push((title, value, false));
+    }
+    let shown = fields.len();
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Verification Queue");
+                if pending.is_empty() {
+                    e.description("No requests are waiting for review.");
+                } else if pending.len() > shown {
+                    e.description(format!(
+                        "Showing the oldest {} of {} pending requests",
+                        shown,
+                        pending.len()
+                    ));
+                } else {
+                    e.description(format!("{} pending requests", pending.len()));
+                }
+                e.fields(fields);
+                e.colour(Colour::BLUE);
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Approves a verification request for an award"]
+#[usage("<Request ID> <Award>")]
+#[min_args(2)]
+#[checks(VibeOfficer)]
+async fn vapprove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<i32>()?;
+    let award = match find_award(ctx, msg, args.rest(), false).await? {
+        Some(a) => a,
+        None => return Ok(()),
+    };
+    let request = match find_pending_request(ctx, msg, id).await? {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+
+    approve_request(ctx, &request, &award, &msg.author, msg.guild_id)
+        .await
+        .map_err(CommandError)?;
+    msg.channel_id
+        .say(
+            &ctx.http,
+            format!("Approved request #{} for {}.", request.id, award.name),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Denies a verification request, sending the reason to the requester so they can resubmit. The reason can be `insufficient`, `wrong-award`, `illegible` or your own text."]
+#[usage("<Request ID> <Reason>")]
+#[min_args(2)]
+#[checks(VibeOfficer)]
+async fn vdeny(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let id = args.single::<i32>()?;
+    let reason = match DenyReason::from_key(args.rest()) {
+        Some(r) => r.description(),
+        None => args.rest().trim(),
+    };
+    let request = match find_pending_request(ctx, msg, id).await? {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+
+    close_request(ctx, &request, &msg.author, msg.guild_id, Some(reason))
+        .await
+        .map_err(CommandError)?;
+    msg.channel_id
+        .say(&ctx.http, format!("Denied request #{}.", request.id))
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Revokes a user's verified award and removes its role in every server. Award names with spaces need quotes."]
+#[usage("<@User> <Award> [Reason]")]
+#[min_args(2)]
+#[checks(VibeOfficer)]
+async fn revoke(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let target = args.single::<UserId>()?;
+    let award = args.single_quoted::<String>()?;
+    let award = match find_award(ctx, msg, &award, true).await? {
+        Some(a) => a,
+        None => return Ok(()),
+    };
+    let reason = match args.rest().trim() {
+        "" => None,
+        r => Some(r.to_string()),
+    };
+
+    let revoked = {
+        let bot_data = ctx.data.read().await;
+        let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+        revoke_verified(pg_pool, target, &award, msg.author.id, reason.as_deref())
+            .await
+            .map_err(CommandError)?
+    };
+    if !revoked {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!("<@{}> doesn't have a verified {} award.", target.as_u64(), award.name),
+            )
+            .await?;
+        return Ok(());
+    }
+    sync_award_roles(ctx, target, &award, false).await;
+    log_verification(
+        ctx,
+        msg.guild_id,
+        &msg.author,
+        target,
+        ModActionType::VerificationRevoked,
+        Some(award.name.clone()),
+        reason.clone(),
+    )
+    .await;
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Verification");
+                e.description(format!(
+                    "Revoked <@{}>'s verified {} award",
+                    target.as_u64(),
+                    award.name
+                ));
+                if let Some(r) = &reason {
+                    e.field("Reason", r, false);
+                }
+                e.colour(Colour::DARK_GREEN);
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command]
+#[description = "Lists every verification type, including retired ones"]
+#[owners_only]
+#[sub_commands(add_type, retire_type, type_expiry)]
+async fn verifytypes(ctx: &Context, msg: &Message) -> CommandResult {
+    let types = {
+        let bot_data = ctx.data.read().await;
+        let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+        get_verification_types(pg_pool, true)
+            .await
+            .map_err(CommandError)?
+    };
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Verification Types");
+                e.description("Usage: `verifytypes add <key> <emoji> <Name> | <Proof>`, `verifytypes retire <key>`, `verifytypes expiry <key> <days/never>`");
+                e.fields(types.iter().map(|t| {
+                    (
+                        format!(
+                            "{} {}{}",
+                            t.emoji,
+                            t.name,
+                            if t.retired { " (retired)" } else { "" }
+                        ),
+                        match t.valid_days {
+                            Some(days) => format!("`{}`, expires after {} days\n{}", t.key, days, t.proof),
+                            None => format!("`{}`\n{}", t.key, t.proof),
+                        },
+                        true,
+                    )
+                }));
+                e.colour(Colour::BLUE);
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await?;
+
+    Ok(())
+}
+
+#[command("add")]
+#[description = "Adds a verification type, or brings back a retired one with the same key. The key names its `verified_role_<key>` server setting."]
+#[usage("<Key> <Emoji> <Name> | <Proof>")]
+#[min_args(3)]
+#[owners_only]
+async fn add_type(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let key = args.single::<String>()?.to_lowercase();
+    let emoji = args.single::<String>()?;
+    let mut rest = args.rest().splitn(2, '|');
+    let name = rest.next().unwrap_or("").trim().to_string();
+    let proof = rest.next().unwrap_or("").trim().to_string();
+    if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') || name.is_empty() || proof.is_empty() {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: `verifytypes add <key> <emoji> <Name> | <Proof>`. Keys may only use letters, numbers and underscores.",
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let types = get_verification_types(pg_pool, false)
+        .await
+        .map_err(CommandError)?;
+    let emoji_taken = emoji == CLOSE_EMOJI
+        || match_deny_emoji(&emoji)
+        || types.iter().any(|t| t.emoji == emoji && t.key != key);
+    if emoji_taken {
+        msg.channel_id
+            .say(&ctx.http, format!("{} is already used on the verification queue.", emoji))
+            .await?;
+        return Ok(());
+    }
+    if types.len() >= MAX_VERIFICATION_TYPES && !types.iter().any(|t| t.key == key) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "There can be at most {} active verification types, since each needs a reaction on the queue message.",
+                    MAX_VERIFICATION_TYPES
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "INSERT INTO verification_types (key, name, emoji, proof) VALUES ($1, $2, $3, $4) ON CONFLICT (key) DO UPDATE SET name = EXCLUDED.name, emoji = EXCLUDED.emoji, proof = EXCLUDED.proof, retired = FALSE",
+        key,
+        name,
+        emoji,
+        proof
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(|e| CommandError(e.to_string()))?;
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Verification Types");
+                e.description(format!("Added {} {}", emoji, name));
+                e.field("Key", &key, true);
+                e.field("Role Setting", format!("verified_role_{}", key), true);
+                e.field("Proof", &proof, false);
+                e.colour(Colour::DARK_GREEN);
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await?;
+    warn!("{} added verification type {}", &msg.author.name, key);
+
+    Ok(())
+}
+
+#[command("retire")]
+#[description = "Retires a verification type so it can no longer be requested or approved. Existing verifications and roles are kept."]
+#[usage("<Key>")]
+#[num_args(1)]
+#[owners_only]
+async fn retire_type(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let key = args.rest().trim().to_lowercase();
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let retired = sqlx::query!(
+        "UPDATE verification_types SET retired = TRUE WHERE key = $1 AND NOT retired",
+        key
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(|e| CommandError(e.to_string()))?;
+    if retired == 0 {
+        msg.channel_id
+            .say(&ctx.http, format!("There is no active verification type `{}`.", key))
+            .await?;
+        return Ok(());
+    }
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Verification Types");
+                e.description(format!("Retired `{}`", key));
+                e.colour(Colour::DARK_GREEN);
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await?;
+    warn!("{} retired verification type {}", &msg.author.name, key);
+
+    Ok(())
+}
+
+#[command("expiry")]
+#[description = "Sets how many days a verification type lasts before it has to be renewed, or `never`. Only affects verifications approved afterwards."]
+#[usage("<Key> <Days/never>")]
+#[num_args(2)]
+#[owners_only]
+async fn type_expiry(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let key = args.single::<String>()?.to_lowercase();
+    let valid_days = match args.single::<String>()?.as_str() {
+        "never" => None,
+        days => match days.parse::<i32>() {
+            Ok(d) if d > 0 => Some(d),
+            _ => {
+                msg.channel_id
+                    .say(&ctx.http, "Expiry must be a number of days or `never`.")
+                    .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let updated = sqlx::query!(
+        "UPDATE verification_types SET valid_days = $2 WHERE key = $1",
+        key,
+        valid_days
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(|e| CommandError(e.to_string()))?;
+    if updated == 0 {
+        msg.channel_id
+            .say(&ctx.http, format!("There is no verification type `{}`.", key))
+            .await?;
+        return Ok(());
+    }
+
+    msg.channel_id
+        .send_message(&ctx.http, |m| {
+            m.embed(|e| {
+                e.title("Verification Types");
+                e.description(match valid_days {
+                    Some(days) => format!("`{}` verifications now expire after {} days", key, days),
+                    None => format!("`{}` verifications no longer expire", key),
+                });
+                e.colour(Colour::DARK_GREEN);
+                e.footer(|f| {
+                    f.text(format!("Requested by {}", &msg.author.name));
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await?;
+    warn!("{} set verification type {} expiry to {:?}", &msg.author.name, key, valid_days);
+
+    Ok(())
+}
+
+/// Reads an award by key or name, telling the reviewer if there's no such award.
+/// Retired awards are only found when `include_retired` is set.
+async fn find_award(
+    ctx: &Context,
+    msg: &Message,
+    input: &str,
+    include_retired: bool,
+) -> Result<Option<VerificationType>, CommandError> {
+    let types = {
+        let bot_data = ctx.data.read().await;
+        let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+        get_verification_types(pg_pool, include_retired)
+            .await
+            .map_err(CommandError)?
+    };
+    if let Some(award) = find_verification_type(&types, input) {
+        return Ok(Some(award.clone()));
+    }
+    msg.channel_id
+        .say(&ctx.http, format!("{} isn't an award that can be verified.", input.trim()))
+        .await?;
+
+    Ok(None)
+}
+
+/// Fetches a request for `vapprove` or `vdeny`, telling the reviewer if it can't be reviewed.
+async fn find_pending_request(
+    ctx: &Context,
+    msg: &Message,
+    id: i32,
+) -> Result<Option<VerificationRequest>, CommandError> {
+    let request = {
+        let bot_data = ctx.data.read().await;
+        let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+        get_request(pg_pool, id).await.map_err(CommandError)?
+    };
+    let problem = match &request {
+        None => format!("There is no verification request #{}.", id),
+        Some(r) if r.status != "pending" => format!("Request #{} has already been {}.", id, r.status),
+        Some(_) => return Ok(request),
+    };
+    msg.channel_id.say(&ctx.http, problem).await?;
+
+    Ok(None)
+}
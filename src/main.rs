@@ -16,6 +16,7 @@ use serenity::{
     http::Http,
     model::{
         channel::{Message, Reaction},
+        event::MessageUpdateEvent,
         gateway::{Activity, Ready},
         guild::{Guild, Member},
         id::{ChannelId, GuildId, UserId},
@@ -24,7 +25,11 @@ use serenity::{
     prelude::*,
     utils::Colour,
 };
-use std::{collections::HashSet, env};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    sync::Arc,
+};
 
 use log::{debug, error, info};
 
@@ -34,7 +39,6 @@ mod checks;
 mod commands;
 pub mod models;
 mod util;
-use models::Dban;
 /*use crate::commands::{
     badges::*, general::*, moderation::*, owner::*, settings::*, verification::*,
 };*/
@@ -56,7 +60,7 @@ impl TypeMapKey for ConnectionPool {
 struct General;
 
 #[group]
-#[commands(restart, initcache)]
+#[commands(restart, initcache, importdata)]
 struct Owner;
 
 #[group]
@@ -70,10 +74,13 @@ struct Owner;
     runuser,
     syncbans,
     advise,
+    advisories,
+    modadvisory,
     modban,
     bans,
     raid,
-    unraid
+    unraid,
+    modlog
 )]
 struct Moderation;
 
@@ -82,7 +89,7 @@ struct Moderation;
 struct Settings;
 
 #[group]
-#[commands(age, verify)]
+#[commands(age, verify, vqueue, vapprove, vdeny, revoke, verifytypes)]
 struct Verification;
 
 #[group]
@@ -96,10 +103,12 @@ impl EventHandler for Handler {
         info!("Logged in to Discord successfully");
         let activity = Activity::playing("with vibes");
         ctx.set_presence(Some(activity), OnlineStatus::DoNotDisturb);
+        raid::resume_raids(&ctx).await;
+        verification::schedule_verification_expiry(ctx);
     }
 
     async fn reaction_add(&self, ctx: Context, add_reaction: Reaction) {
-        if add_reaction.channel_id.as_u64() == &684577265425973285u64 {
+        if add_reaction.channel_id == verification::VERIFY_CHANNEL {
             match verification::handle_verification_reaction(&ctx, add_reaction).await {
                 Err(e) => error!("Error verifying user. {}", e),
                 _ => (),
@@ -121,46 +130,83 @@ impl EventHandler for Handler {
                 return;
             }
         };
-        if util::moderation::contains_banned_word(&msg.content, &guild.as_u64()) {
-            msg.channel_id
-                .send_message(&ctx.http, |m| {
-                    m.embed(|e| {
-                        let mut mention = String::from("<@");
-                        mention.push_str(&msg.author.id.as_u64().to_string());
-                        mention.push_str(">");
+        match wordfilter::filter_message(&ctx, &msg).await {
+            Ok(true) => return,
+            Ok(false) => (),
+            Err(e) => {
+                error!("Error acting on filtered message in {}: {}", guild.as_u64(), e);
+                return;
+            }
+        }
 
-                        e.title("Warning - Bad Language");
-                        e.description("Do not use poor language or slurs in this server.");
-                        e.fields(vec![("User:", mention, false)]);
+        //* Spam
+        let bot_data = ctx.data.read().await;
+        let message_tracker = bot_data.get::<spam::MessageTracker>().unwrap();
+        if let Err(e) = spam::check_spam(&ctx, message_tracker, &msg).await {
+            error!("Error acting on spam in {}: {}", guild.as_u64(), e);
+        }
+    }
 
-                        e.color(Colour::RED);
+    async fn message_update(
+        &self,
+        ctx: Context,
+        _old_if_available: Option<Message>,
+        new: Option<Message>,
+        event: MessageUpdateEvent,
+    ) {
+        // Only edits to the content need checking, not embeds being resolved
+        if event.content.is_none() {
+            return;
+        }
+        let guild = match event.guild_id {
+            Some(id) => id,
+            None => return,
+        };
+        let mut msg = match new {
+            Some(m) => m,
+            None => match event.channel_id.message(&ctx.http, event.id).await {
+                Ok(m) => m,
+                Err(e) => {
+                    error!("Error fetching edited message: {:?}", e);
+                    return;
+                }
+            },
+        };
+        // Messages fetched over HTTP don't carry their guild
+        msg.guild_id = Some(guild);
 
-                        e
-                    });
+        debug!("Checking edited message against banned words list");
+        if let Err(e) = wordfilter::filter_message(&ctx, &msg).await {
+            error!("Error acting on filtered edit in {}: {}", guild.as_u64(), e);
+        }
+    }
 
-                    m
-                })
-                .await
-                .unwrap();
-            let action = moderation::ModAction {
-                target: msg.author.clone().id,
-                moderator: ctx
-                    .http
-                    .get_current_application_info()
-                    .await
-                    .unwrap()
-                    .id
-                    .to_user(&ctx)
-                    .await
-                    .unwrap()
-                    .clone(),
-                reason: Some(String::from("Found a banned word")),
-                details: None,
-                action_type: moderation::ModActionType::BadWordDelete,
-                guild: msg.guild_id.clone().unwrap(),
-            };
-            moderation::log_mod_action(action, &mut ctx.clone()).await;
-            msg.delete(&ctx).await.unwrap();
+    async fn guild_member_update(
+        &self,
+        ctx: Context,
+        old_if_available: Option<Member>,
+        new: Member,
+    ) {
+        let nick = match &new.nick {
+            Some(n) => n,
+            None => return,
+        };
+        if let Some(old) = &old_if_available {
+            if old.nick.as_ref() == Some(nick) {
+                return;
+            }
+        }
+
+        if let Err(e) = wordfilter::filter_member_name(
+            &ctx,
+            new.guild_id,
+            &new,
+            nick,
+            "word_filter_nickname_action",
+        )
+        .await
+        {
+            error!("Error acting on filtered nickname: {}", e);
         }
     }
 
@@ -178,20 +224,45 @@ impl EventHandler for Handler {
                 }
             }
         }
-        if let Err(err) = sqlx::query!(
-            "INSERT INTO dbans (userid,reason,guild_id) VALUES ($1,$2,$3)",
+        if reason.starts_with(moderation::AUTO_BAN_PREFIX) {
+            debug!("Skipping ban issued from the DSC banlist");
+            return;
+        }
+        let case_id = match sqlx::query!(
+            "INSERT INTO dbans (userid,reason,guild_id) VALUES ($1,$2,$3) RETURNING id",
             banned_user.id.as_u64().to_string(),
             &reason,
             guild_id.as_u64().to_string()
         )
-        .execute(pg_pool)
+        .fetch_one(pg_pool)
         .await
         {
-            error!(
-                "Encountered an error adding a ban for {}: {:?}",
-                banned_user.name, err
-            );
+            Ok(row) => Some(row.id),
+            Err(err) => {
+                error!(
+                    "Encountered an error adding a ban for {}: {:?}",
+                    banned_user.name, err
+                );
+                None
+            }
         };
+        match moderation::get_bot_user(&ctx).await {
+            Ok(moderator) => {
+                let action = moderation::ModAction {
+                    target: Some(banned_user.id),
+                    moderator,
+                    action_type: moderation::ModActionType::BanDetected,
+                    reason: Some(reason.clone()),
+                    details: Some(String::from("Ban added to the DSC Banlist")),
+                    guild: guild_id,
+                    case_id,
+                    old_value: None,
+                    new_value: None,
+                };
+                moderation::log_mod_action(action, &ctx).await;
+            }
+            Err(err) => error!("Could not fetch bot user: {}", err),
+        }
         let blacklist_channel = ctx.http.get_channel(646545388576178178).await.unwrap();
         let blacklist_channel_id = blacklist_channel.id();
         let guild = ctx.http.get_guild(guild_id.as_u64().clone()).await.unwrap();
@@ -226,16 +297,89 @@ impl EventHandler for Handler {
         }
     }
 
-    async fn guild_create(&self, _ctx: Context, guild: Guild, _is_new: bool) {
-        if _is_new {
-            info!(
-                "Joined new guild {}. Intializing guild settings.",
-                &guild.name
+    async fn guild_ban_removal(&self, ctx: Context, guild_id: GuildId, unbanned_user: User) {
+        let bot_data = &ctx.data.read().await;
+        let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+
+        let withdrawn = match sqlx::query!(
+            "UPDATE dbans SET is_withdrawn = 't' WHERE userid = $1 AND guild_id = $2 AND is_withdrawn = 'f' RETURNING id",
+            unbanned_user.id.as_u64().to_string(),
+            guild_id.as_u64().to_string()
+        )
+        .fetch_all(pg_pool)
+        .await
+        {
+            Ok(rows) => rows.iter().map(|r| r.id).collect::<Vec<i32>>(),
+            Err(err) => {
+                error!(
+                    "Encountered an error withdrawing bans for {}: {:?}",
+                    unbanned_user.name, err
+                );
+                return;
+            }
+        };
+        if withdrawn.is_empty() {
+            return;
+        }
+        let cases = withdrawn
+            .iter()
+            .map(|id| format!("#{}", id))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        let guild = ctx.http.get_guild(guild_id.as_u64().clone()).await.unwrap();
+        if let Err(err) = ChannelId(prelude::NOTIFY_CHANNEL)
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Ban Lifted");
+                    e.fields(vec![
+                        ("Server", &guild.name, false),
+                        (
+                            "Name",
+                            &format!("{}#{}", &unbanned_user.name, &unbanned_user.discriminator),
+                            false,
+                        ),
+                        ("ID", &unbanned_user.id.as_u64().to_string(), false),
+                        ("Withdrawn Cases", &cases, false),
+                    ]);
+                    e.colour(Colour::BLUE);
+                    e
+                });
+                m
+            })
+            .await
+        {
+            error!(
+                "Encountered an error trying to notify DSC about a lifted ban for {}: {:?}",
+                unbanned_user.name, err
             );
         }
-        let mut cache = data::get_pickle_database(&guild.id.as_u64(), "settings.db");
-        if let None = cache.get::<String>("modlogs_channel") {
-            data::init_guild_settings(&mut cache);
+
+        let moderator = match moderation::get_bot_user(&ctx).await {
+            Ok(u) => u,
+            Err(err) => {
+                error!("Could not fetch bot user: {}", err);
+                return;
+            }
+        };
+        let action = moderation::ModAction {
+            target: Some(unbanned_user.id),
+            moderator,
+            action_type: moderation::ModActionType::Unban,
+            reason: Some(String::from("User was unbanned from the server")),
+            details: Some(format!("Withdrew DSC ban case(s) {}", cases)),
+            guild: guild_id,
+            case_id: None,
+            old_value: None,
+            new_value: None,
+        };
+        moderation::log_mod_action(action, &ctx).await;
+    }
+
+    async fn guild_create(&self, _ctx: Context, guild: Guild, _is_new: bool) {
+        // Guild settings start out at their defaults, so there is nothing to initialize
+        if _is_new {
+            info!("Joined new guild {}", &guild.name);
         }
     }
 
@@ -244,34 +388,60 @@ impl EventHandler for Handler {
         let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
 
         let user_id = new_member.user.id;
-        let member_id = user_id.as_u64();
-        let mut is_banned = false;
-        let mut reason = String::from("No reason provided");
-
-        let result = match sqlx::query_as!(
-            Dban,
-            "SELECT * FROM dbans WHERE userid = $1",
-            member_id.to_string()
+
+        if let Err(err) = raid::gate_raid_join(&ctx, pg_pool, guild_id, &new_member).await {
+            error!("Error applying raid mode to a new member: {}", err);
+        }
+        let join_tracker = bot_data.get::<raid::JoinTracker>().unwrap();
+        if let Err(err) =
+            raid::track_join(&ctx, pg_pool, join_tracker, guild_id, &new_member).await
+        {
+            error!("Error checking joins for a raid: {}", err);
+        }
+        if let Err(err) = wordfilter::filter_member_name(
+            &ctx,
+            guild_id,
+            &new_member,
+            &new_member.user.name,
+            "word_filter_username_action",
         )
-        .fetch_one(pg_pool)
         .await
         {
-            Ok(dban) => dban,
+            error!("Error acting on filtered username: {}", err);
+        }
+        let mut verified_member = new_member.clone();
+        if let Err(err) =
+            verification::grant_verified_roles(&ctx, pg_pool, guild_id, &mut verified_member).await
+        {
+            error!("Error granting verified roles to a new member: {}", err);
+        }
+
+        let bans = match moderation::get_active_dbans(pg_pool, user_id).await {
+            Ok(b) => b,
             Err(e) => {
                 error!("SQL Error: {:?}", e);
                 return;
             }
         };
-
-        if result.is_withdrawn != true {
-            is_banned = true;
-            reason = result.reason;
+        if bans.is_empty() {
+            return;
         }
+        let reason = bans
+            .iter()
+            .map(|b| b.reason.as_str())
+            .collect::<Vec<&str>>()
+            .join("; ");
 
         let guild_arc = guild_id.to_guild_cached(&ctx).await.unwrap();
         let guild = guild_arc.read().await;
 
-        let settings = data::get_pickle_database(&guild_id.as_u64(), "settings.db");
+        let settings = match settings::GuildSettings::load(pg_pool, guild_id).await {
+            Ok(s) => s,
+            Err(e) => {
+                error!("Error loading settings for {}: {}", guild_id.as_u64(), e);
+                return;
+            }
+        };
         let alert_channel: ChannelId;
         let temp_channel = match settings.get::<u64>("modlogs_channel") {
             Some(channel) => channel,
@@ -288,43 +458,68 @@ impl EventHandler for Handler {
         } else {
             alert_channel = temp_channel.into();
         }
-        if is_banned {
-            let user = &ctx
-                .http
-                .get_user(*new_member.user.id.as_u64())
-                .await
-                .unwrap();
-            match alert_channel
-                .send_message(&ctx, |m| {
-                    m.embed(|e| {
-                        e.title("Alert!");
-                        e.description("A banned user has joined the server.");
-                        e.field(
-                            "User",
-                            format!("{}#{}", user.name, user.discriminator),
-                            true,
-                        );
-                        e.field("Reason", reason, true);
-                        e.footer(|f| {
-                            f.text(format!("DSC Bot | Powered by Rusty Developers"));
-                            f
-                        });
-                        e.colour(Colour::RED);
-
-                        e
-                    });
-                    m
-                })
-                .await
-            {
+        let user = &ctx
+            .http
+            .get_user(*new_member.user.id.as_u64())
+            .await
+            .unwrap();
+
+        let source_servers = moderation::count_ban_sources(&bans) as u64;
+        let ban_list = moderation::describe_dbans(&ctx, &bans).await;
+        let min_servers = settings.get::<u64>("banned_join_min_servers").unwrap_or(1);
+        let action_taken = if source_servers >= min_servers {
+            match moderation::act_on_banned_join(&ctx, guild_id, &new_member, &reason).await {
+                Ok(Some(action)) => action.to_string(),
+                Ok(None) => String::from("None"),
                 Err(err) => {
                     error!(
-                        "Encountered an error warning {} about {}#{}: {:?}",
-                        &guild.name, user.name, user.discriminator, err
+                        "Error acting on banned user joining {}: {}",
+                        &guild.name, err
                     );
+                    format!("Failed: {}", err)
                 }
-                _ => (),
             }
+        } else {
+            format!(
+                "None (banned in {} of {} required servers)",
+                source_servers, min_servers
+            )
+        };
+
+        match alert_channel
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Alert!");
+                    e.description(format!(
+                        "A user banned from {} DSC member server(s) has joined the server.",
+                        source_servers
+                    ));
+                    e.field(
+                        "User",
+                        format!("{}#{}", user.name, user.discriminator),
+                        true,
+                    );
+                    e.field("Action Taken", action_taken, true);
+                    e.field("Bans", &ban_list, false);
+                    e.footer(|f| {
+                        f.text(format!("DSC Bot | Powered by Rusty Developers"));
+                        f
+                    });
+                    e.colour(Colour::RED);
+
+                    e
+                });
+                m
+            })
+            .await
+        {
+            Err(err) => {
+                error!(
+                    "Encountered an error warning {} about {}#{}: {:?}",
+                    &guild.name, user.name, user.discriminator, err
+                );
+            }
+            _ => (),
         }
     }
 }
@@ -505,6 +700,10 @@ async fn main() {
             }
         };
         data.insert::<ConnectionPool>(pool.clone());
+        data.insert::<raid::JoinTracker>(Arc::new(Mutex::new(HashMap::new())));
+        data.insert::<wordfilter::WordFilterCache>(Arc::new(RwLock::new(wordfilter::FilterCache::default())));
+        data.insert::<spam::MessageTracker>(Arc::new(Mutex::new(HashMap::new())));
+        data.insert::<spam::InviteCache>(Arc::new(Mutex::new(HashMap::new())));
     }
 
     info!("Starting client");
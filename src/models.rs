@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize)]
@@ -14,6 +15,10 @@ pub struct StrikeLog {
   pub id: i32,
   pub moderator: String,
   pub reason: String,
+  pub details: Option<String>,
+  pub is_withdrawn: bool,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Deserialize)]
@@ -21,7 +26,10 @@ pub struct GetStrike {
   pub userid: String,
   pub moderator: String,
   pub reason: String,
+  pub details: Option<String>,
   pub is_withdrawn: bool,
+  pub created_at: DateTime<Utc>,
+  pub expires_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Deserialize, Default)]
@@ -35,3 +43,86 @@ pub struct DbanList {
   pub reason: String,
   pub is_withdrawn: bool,
 }
+
+#[derive(Deserialize)]
+pub struct Advisory {
+  pub id: i32,
+  pub guild_id: String,
+  pub moderator: String,
+  pub reason: String,
+  pub created_at: DateTime<Utc>,
+  pub is_withdrawn: bool,
+}
+
+#[derive(Deserialize)]
+pub struct Raid {
+  pub guild_id: String,
+  pub moderator: String,
+  pub lockdown: bool,
+  pub join_action: String,
+  pub started_at: DateTime<Utc>,
+  pub ends_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct RaidChannel {
+  pub channel_id: String,
+  pub slow_mode_rate: i32,
+  pub everyone_allow: Option<i64>,
+  pub everyone_deny: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ModActionLog {
+  pub id: i32,
+  pub target: Option<String>,
+  pub moderator: String,
+  pub action_type: String,
+  pub reason: Option<String>,
+  pub details: Option<String>,
+  pub case_id: Option<i32>,
+  pub old_value: Option<String>,
+  pub new_value: Option<String>,
+  pub created_at: DateTime<Utc>,
+}
+
+#[derive(Deserialize)]
+pub struct VerificationRequest {
+  pub id: i32,
+  pub requester: String,
+  pub attachments: String,
+  pub requested_award: Option<String>,
+  pub status: String,
+  pub reviewer: Option<String>,
+  pub decided_at: Option<DateTime<Utc>>,
+  pub deny_reason: Option<String>,
+  pub queue_message: Option<String>,
+  pub created_at: DateTime<Utc>,
+  pub resubmissions: i32,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct VerificationType {
+  pub id: i32,
+  pub key: String,
+  pub name: String,
+  pub emoji: String,
+  pub proof: String,
+  pub retired: bool,
+  pub created_at: DateTime<Utc>,
+  pub valid_days: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct Verification {
+  pub userid: String,
+  pub award: String,
+  pub verified_at: DateTime<Utc>,
+  pub id: i32,
+  pub approver: Option<String>,
+  pub expires_at: Option<DateTime<Utc>>,
+  pub expiry_notified: bool,
+  pub revoked_at: Option<DateTime<Utc>>,
+  pub revoked_by: Option<String>,
+  pub revoke_reason: Option<String>,
+}
@@ -44,11 +44,6 @@ fn create_directories(path: &String) {
     create_dir_all(path).unwrap();
 }
 
-pub fn init_guild_settings(db: &mut PickleDb) {
-    //* Question of the Day
-    db.set("modlogs_channel", &0u64);
-}
-
 pub async fn obtain_pg_pool() -> Result<PgPool, Box<dyn std::error::Error>> {
     let url = match env::var("DATABASE_URL") {
         Ok(u) => u,
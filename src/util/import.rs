@@ -0,0 +1,238 @@
+/*
+ *   Copyright (c) 2020 Owen Salter <owen@devosmium.xyz>
+ *   All rights reserved.
+ */
+
+//* One-shot imports from the old pickledb files under ./data. Rows already in Postgres are
+//* kept, so running an import twice doesn't undo changes made since the first run.
+
+use super::settings::{SettingType, SettingValue, GUILD_SETTINGS};
+use super::verification::get_verification_types;
+use super::wordfilter::{add_filter_entry, allow_word, FilterEntry, MatchMode};
+use pickledb::{PickleDb, PickleDbDumpPolicy};
+use serenity::model::id::GuildId;
+use sqlx::PgPool;
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+
+/// Opens an existing pickle database without creating it or writing anything back.
+fn open_pickle(path: &Path) -> Option<PickleDb> {
+    PickleDb::load_yaml(path, PickleDbDumpPolicy::NeverDump).ok()
+}
+
+/// Every `./data/<guild id>` directory.
+pub fn guild_data_dirs() -> Result<Vec<(GuildId, PathBuf)>, String> {
+    let mut dirs = Vec::new();
+    for dir in read_dir("./data").map_err(|e| e.to_string())? {
+        let path = dir.map_err(|e| e.to_string())?.path();
+        let guild = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.parse::<u64>().ok());
+        if let (Some(g), true) = (guild, path.is_dir()) {
+            dirs.push((GuildId(g), path));
+        }
+    }
+
+    Ok(dirs)
+}
+
+/// Converts a value from `settings.db` to the unit the setting uses now. The old
+/// `raid_detect_account_age` was in days, where it is now a duration in seconds.
+fn convert_legacy_setting(name: &str, value: SettingValue) -> SettingValue {
+    match (name, value) {
+        ("raid_detect_account_age", SettingValue::Number(days)) => {
+            SettingValue::Number(days.saturating_mul(86400))
+        }
+        (_, value) => value,
+    }
+}
+
+/// Copies each guild's `settings.db` into `guild_settings`. Returns the number of settings imported.
+pub async fn import_settings(pg_pool: &PgPool) -> Result<usize, String> {
+    let mut imported = 0;
+    for (guild, dir) in guild_data_dirs()? {
+        let db = match open_pickle(&dir.join("settings.db")) {
+            Some(d) => d,
+            None => continue,
+        };
+
+        for setting in GUILD_SETTINGS.iter() {
+            let value = match setting.setting_type {
+                SettingType::String(_) => db.get::<String>(&setting.name).map(SettingValue::Text),
+                _ => db.get::<u64>(&setting.name).map(SettingValue::Number),
+            };
+            let value = match value {
+                Some(v) => convert_legacy_setting(&setting.name, v).to_stored(),
+                None => continue,
+            };
+            let name: &str = &setting.name;
+            imported += sqlx::query!(
+                "INSERT INTO guild_settings (guild_id, name, value) VALUES ($1, $2, $3) ON CONFLICT (guild_id, name) DO NOTHING",
+                guild.as_u64().to_string(),
+                name,
+                value
+            )
+            .execute(pg_pool)
+            .await
+            .map_err(|e| e.to_string())? as usize;
+        }
+    }
+
+    Ok(imported)
+}
+
+/// Copies `eagle.db`, `summit.db` and the other award databases into `verified_roles`.
+pub async fn import_verified_roles(pg_pool: &PgPool) -> Result<usize, String> {
+    let mut imported = 0;
+    for award in get_verification_types(pg_pool, true).await? {
+        let db = match open_pickle(Path::new(&format!("./data/{}.db", award.key))) {
+            Some(d) => d,
+            None => continue,
+        };
+
+        for user in db.get_all() {
+            if user.parse::<u64>().is_err() {
+                continue;
+            }
+            // Any earlier row counts, so revoked and expired verifications aren't brought back
+            imported += sqlx::query!(
+                "INSERT INTO verified_roles (userid, award, expires_at) SELECT $1, $2, NOW() + make_interval(days => $3) WHERE NOT EXISTS (SELECT 1 FROM verified_roles WHERE userid = $1 AND award = $2) ON CONFLICT DO NOTHING",
+                user,
+                award.key,
+                award.valid_days
+            )
+            .execute(pg_pool)
+            .await
+            .map_err(|e| e.to_string())? as usize;
+        }
+    }
+
+    Ok(imported)
+}
+
+/// Copies `age.db` into `age_groups`.
+pub async fn import_age_groups(pg_pool: &PgPool) -> Result<usize, String> {
+    let db = match open_pickle(Path::new("./data/age.db")) {
+        Some(d) => d,
+        None => return Ok(0),
+    };
+
+    let mut imported = 0;
+    for user in db.get_all() {
+        let age_group = match db.get::<String>(&user) {
+            Some(a) => a,
+            None => continue,
+        };
+        imported += sqlx::query!(
+            "INSERT INTO age_groups (userid, age_group) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            user,
+            age_group
+        )
+        .execute(pg_pool)
+        .await
+        .map_err(|e| e.to_string())? as usize;
+    }
+
+    Ok(imported)
+}
+
+async fn import_filter_list(pg_pool: &PgPool, path: &Path, guild: Option<GuildId>) -> Result<usize, String> {
+    let db = match open_pickle(path) {
+        Some(d) => d,
+        None => return Ok(0),
+    };
+
+    let mut imported = 0;
+    for word in db.get_all() {
+        // Entries added before matching modes existed are stored as `1`
+        let mode = db
+            .get::<String>(&word)
+            .and_then(|m| MatchMode::from_name(&m))
+            .unwrap_or(MatchMode::Word);
+        let entry = FilterEntry {
+            word,
+            mode,
+            global: guild.is_none(),
+        };
+        if add_filter_entry(pg_pool, guild, &entry).await? {
+            imported += 1;
+        }
+    }
+
+    Ok(imported)
+}
+
+/// Copies the global and per-guild `banned_words.db` files into `banned_words`.
+pub async fn import_word_filter(pg_pool: &PgPool) -> Result<usize, String> {
+    let mut imported = import_filter_list(pg_pool, Path::new("./data/banned_words.db"), None).await?;
+    for (guild, dir) in guild_data_dirs()? {
+        imported += import_filter_list(pg_pool, &dir.join("banned_words.db"), Some(guild)).await?;
+    }
+
+    Ok(imported)
+}
+
+/// Copies each guild's `allowed_words.db` into `allowed_words`.
+pub async fn import_allowed_words(pg_pool: &PgPool) -> Result<usize, String> {
+    let mut imported = 0;
+    for (guild, dir) in guild_data_dirs()? {
+        let db = match open_pickle(&dir.join("allowed_words.db")) {
+            Some(d) => d,
+            None => continue,
+        };
+        for word in db.get_all() {
+            if allow_word(pg_pool, guild, &word).await? {
+                imported += 1;
+            }
+        }
+    }
+
+    Ok(imported)
+}
+
+/// Copies each guild's `wordfilter_exempt.db` into `wordfilter_exemptions`.
+pub async fn import_exemptions(pg_pool: &PgPool) -> Result<usize, String> {
+    let mut imported = 0;
+    for (guild, dir) in guild_data_dirs()? {
+        let db = match open_pickle(&dir.join("wordfilter_exempt.db")) {
+            Some(d) => d,
+            None => continue,
+        };
+        for id in db.get_all() {
+            let kind = db.get::<String>(&id).unwrap_or_else(|| String::from("channel"));
+            imported += sqlx::query!(
+                "INSERT INTO wordfilter_exemptions (guild_id, target_id, kind) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+                guild.as_u64().to_string(),
+                id,
+                kind
+            )
+            .execute(pg_pool)
+            .await
+            .map_err(|e| e.to_string())? as usize;
+        }
+    }
+
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_account_age_is_converted_to_seconds() {
+        let value = convert_legacy_setting("raid_detect_account_age", SettingValue::Number(7));
+        assert_eq!(value.to_stored(), "604800");
+        let value = convert_legacy_setting("raid_detect_account_age", SettingValue::Number(0));
+        assert_eq!(value.to_stored(), "0");
+    }
+
+    #[test]
+    fn other_legacy_settings_are_kept() {
+        let value = convert_legacy_setting("spam_window", SettingValue::Number(10));
+        assert_eq!(value.to_stored(), "10");
+        let value = convert_legacy_setting("spam_action", SettingValue::Text(String::from("kick")));
+        assert_eq!(value.to_stored(), "kick");
+    }
+}
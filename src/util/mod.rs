@@ -3,8 +3,13 @@
  *   All rights reserved.
  */
 pub mod data;
+pub mod import;
 pub mod moderation;
+pub mod raid;
+pub mod settings;
+pub mod spam;
 pub mod verification;
+pub mod wordfilter;
 use crate::prelude::*;
 use serenity::model::{channel::ReactionType, id::ChannelId};
 use std::error::Error;
@@ -47,3 +52,89 @@ pub async fn run_yesno_vote(
 
     Ok(())
 }
+
+/// Longest value Discord accepts in an embed field
+pub const EMBED_FIELD_LIMIT: usize = 1024;
+
+/// Cuts text down to fit in an embed field.
+pub fn truncate_field(text: &str) -> String {
+    truncate_chars(text, EMBED_FIELD_LIMIT)
+}
+
+/// Cuts text down to at most `limit` characters, ending in an ellipsis if anything was cut.
+pub fn truncate_chars(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(limit.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Longest duration accepted, so adding one to the current time can't overflow
+pub const MAX_DURATION_SECS: i64 = 100 * 365 * 24 * 60 * 60;
+
+/// Parses a duration such as `30s`, `30m`, `12h`, `7d` or `2w`. Durations over 100 years
+/// aren't accepted.
+pub fn parse_duration(input: &str) -> Option<chrono::Duration> {
+    let input = input.trim();
+    let (unit_start, unit) = input.char_indices().last()?;
+    let amount = match input[..unit_start].parse::<i64>() {
+        Ok(a) if a > 0 => a,
+        _ => return None,
+    };
+    let unit_secs = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => return None,
+    };
+
+    match amount.checked_mul(unit_secs) {
+        Some(secs) if secs <= MAX_DURATION_SECS => Some(chrono::Duration::seconds(secs)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_field_fits_embed_limit() {
+        assert_eq!(truncate_field("short"), "short");
+        let long = "é".repeat(2000);
+        assert_eq!(truncate_field(&long).chars().count(), EMBED_FIELD_LIMIT);
+        assert!(truncate_field(&long).ends_with('…'));
+    }
+
+    #[test]
+    fn parse_duration_reads_each_unit() {
+        assert_eq!(parse_duration("30s"), Some(chrono::Duration::seconds(30)));
+        assert_eq!(parse_duration("10m"), Some(chrono::Duration::minutes(10)));
+        assert_eq!(parse_duration(" 12h "), Some(chrono::Duration::hours(12)));
+        assert_eq!(parse_duration("7d"), Some(chrono::Duration::days(7)));
+        assert_eq!(parse_duration("2w"), Some(chrono::Duration::weeks(2)));
+    }
+
+    #[test]
+    fn parse_duration_rejects_bad_input() {
+        assert_eq!(parse_duration(""), None);
+        assert_eq!(parse_duration("d"), None);
+        assert_eq!(parse_duration("0d"), None);
+        assert_eq!(parse_duration("-5d"), None);
+        assert_eq!(parse_duration("5y"), None);
+        assert_eq!(parse_duration("5é"), None);
+        assert_eq!(parse_duration("é"), None);
+    }
+
+    #[test]
+    fn parse_duration_rejects_huge_amounts() {
+        assert_eq!(parse_duration("9223372036854775807w"), None);
+        assert_eq!(parse_duration("99999999999d"), None);
+        assert!(parse_duration("36500d").is_some());
+        assert_eq!(parse_duration("36600d"), None);
+    }
+}
@@ -3,85 +3,492 @@
  *   All rights reserved.
  */
 
-use super::data::{get_global_pickle_database, get_pickle_database};
+use super::settings::{get_guild_settings, GuildSettings};
+use super::truncate_field;
+use log::{debug, error};
 use serenity::client::Context;
 use serenity::model::{id::GuildId, prelude::*, user::User};
+use crate::models::Dban;
+use crate::ConnectionPool;
+use sqlx::PgPool;
+use std::collections::HashSet;
+
+/// Valid values for the `on_banned_join` guild setting.
+pub const BANNED_JOIN_MODES: [&str; 4] = ["alert", "quarantine", "kick", "ban"];
+
+/// Prefix on the reason of bans the bot issues because of the DSC banlist. These bans
+/// are not re-uploaded to the banlist, so they don't count as another source server.
+pub const AUTO_BAN_PREFIX: &str = "[DSC Banlist]";
 
 pub enum ModActionType {
     Strike,
     BadWordDelete,
     ClearStrikes,
+    ModifyStrike,
+    Timeout,
+    Kick,
+    Ban,
+    Quarantine,
+    Unban,
+    BanDetected,
+    ModifyBan,
+    BanSync,
+    Advisory,
+    ModifyAdvisory,
+    Raid,
+    Unraid,
+    BadgeAdd,
+    BadgeRemove,
+    WordFilterAdd,
+    WordFilterAllow,
+    WordFilterExempt,
+    WordFilterImport,
+    WordFilterRemove,
+    NameFilter,
+    Spam,
+    SettingChange,
+    SettingsReset,
+    Verification,
+    VerificationClosed,
+    VerificationDenied,
+    VerificationRevoked,
+}
+
+impl ModActionType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ModActionType::Strike => "Strike",
+            ModActionType::BadWordDelete => "Word Filter",
+            ModActionType::ClearStrikes => "Strikelog Clear",
+            ModActionType::ModifyStrike => "Strike Modified",
+            ModActionType::Timeout => "Timeout",
+            ModActionType::Kick => "Kick",
+            ModActionType::Ban => "Ban",
+            ModActionType::Quarantine => "Quarantine",
+            ModActionType::Unban => "Unban",
+            ModActionType::BanDetected => "Ban Detected",
+            ModActionType::ModifyBan => "DSC Ban Modified",
+            ModActionType::BanSync => "Ban Sync",
+            ModActionType::Advisory => "Advisory",
+            ModActionType::ModifyAdvisory => "Advisory Modified",
+            ModActionType::Raid => "Raid Mode",
+            ModActionType::Unraid => "Raid Mode Lifted",
+            ModActionType::BadgeAdd => "Badge Added",
+            ModActionType::BadgeRemove => "Badge Removed",
+            ModActionType::WordFilterAdd => "Word Filter Entry Added",
+            ModActionType::WordFilterAllow => "Word Filter Word Allowed",
+            ModActionType::WordFilterExempt => "Word Filter Exemption Changed",
+            ModActionType::WordFilterImport => "Word Filter Import",
+            ModActionType::WordFilterRemove => "Word Filter Entry Removed",
+            ModActionType::NameFilter => "Name Filter",
+            ModActionType::Spam => "Spam Removed",
+            ModActionType::SettingChange => "Setting Changed",
+            ModActionType::SettingsReset => "Settings Reset",
+            ModActionType::Verification => "Verification Approved",
+            ModActionType::VerificationClosed => "Verification Closed",
+            ModActionType::VerificationDenied => "Verification Denied",
+            ModActionType::VerificationRevoked => "Verification Revoked",
+        }
+    }
+
+    /// Stable identifier stored in the `mod_actions` table.
+    pub fn key(&self) -> &'static str {
+        match self {
+            ModActionType::Strike => "strike",
+            ModActionType::BadWordDelete => "bad_word_delete",
+            ModActionType::ClearStrikes => "clear_strikes",
+            ModActionType::ModifyStrike => "modify_strike",
+            ModActionType::Timeout => "timeout",
+            ModActionType::Kick => "kick",
+            ModActionType::Ban => "ban",
+            ModActionType::Quarantine => "quarantine",
+            ModActionType::Unban => "unban",
+            ModActionType::BanDetected => "ban_detected",
+            ModActionType::ModifyBan => "modify_ban",
+            ModActionType::BanSync => "ban_sync",
+            ModActionType::Advisory => "advisory",
+            ModActionType::ModifyAdvisory => "modify_advisory",
+            ModActionType::Raid => "raid",
+            ModActionType::Unraid => "unraid",
+            ModActionType::BadgeAdd => "badge_add",
+            ModActionType::BadgeRemove => "badge_remove",
+            ModActionType::WordFilterAdd => "word_filter_add",
+            ModActionType::WordFilterAllow => "word_filter_allow",
+            ModActionType::WordFilterExempt => "word_filter_exempt",
+            ModActionType::WordFilterImport => "word_filter_import",
+            ModActionType::WordFilterRemove => "word_filter_remove",
+            ModActionType::NameFilter => "name_filter",
+            ModActionType::Spam => "spam",
+            ModActionType::SettingChange => "setting_change",
+            ModActionType::SettingsReset => "settings_reset",
+            ModActionType::Verification => "verification",
+            ModActionType::VerificationClosed => "verification_closed",
+            ModActionType::VerificationDenied => "verification_denied",
+            ModActionType::VerificationRevoked => "verification_revoked",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<ModActionType> {
+        match key {
+            "strike" => Some(ModActionType::Strike),
+            "bad_word_delete" => Some(ModActionType::BadWordDelete),
+            "clear_strikes" => Some(ModActionType::ClearStrikes),
+            "modify_strike" => Some(ModActionType::ModifyStrike),
+            "timeout" => Some(ModActionType::Timeout),
+            "kick" => Some(ModActionType::Kick),
+            "ban" => Some(ModActionType::Ban),
+            "quarantine" => Some(ModActionType::Quarantine),
+            "unban" => Some(ModActionType::Unban),
+            "ban_detected" => Some(ModActionType::BanDetected),
+            "modify_ban" => Some(ModActionType::ModifyBan),
+            "ban_sync" => Some(ModActionType::BanSync),
+            "advisory" => Some(ModActionType::Advisory),
+            "modify_advisory" => Some(ModActionType::ModifyAdvisory),
+            "raid" => Some(ModActionType::Raid),
+            "unraid" => Some(ModActionType::Unraid),
+            "badge_add" => Some(ModActionType::BadgeAdd),
+            "badge_remove" => Some(ModActionType::BadgeRemove),
+            "word_filter_add" => Some(ModActionType::WordFilterAdd),
+            "word_filter_allow" => Some(ModActionType::WordFilterAllow),
+            "word_filter_exempt" => Some(ModActionType::WordFilterExempt),
+            "word_filter_import" => Some(ModActionType::WordFilterImport),
+            "word_filter_remove" => Some(ModActionType::WordFilterRemove),
+            "name_filter" => Some(ModActionType::NameFilter),
+            "spam" => Some(ModActionType::Spam),
+            "setting_change" => Some(ModActionType::SettingChange),
+            "settings_reset" => Some(ModActionType::SettingsReset),
+            "verification" => Some(ModActionType::Verification),
+            "verification_closed" => Some(ModActionType::VerificationClosed),
+            "verification_denied" => Some(ModActionType::VerificationDenied),
+            "verification_revoked" => Some(ModActionType::VerificationRevoked),
+            _ => None,
+        }
+    }
 }
 
 pub struct ModAction {
-    pub target: UserId,
+    /// The user acted on, if the action targets one
+    pub target: Option<UserId>,
     pub moderator: User,
     pub action_type: ModActionType,
     pub reason: Option<String>,
     pub details: Option<String>,
     pub guild: GuildId,
+    /// Strike, ban or advisory case the action refers to
+    pub case_id: Option<i32>,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
 }
 
 impl ModAction {}
 
-pub fn contains_banned_word(content: &String, guild_id: &u64) -> bool {
-    let mut global_db = get_global_pickle_database("banned_words.db");
-    let local_db = get_pickle_database(guild_id, "banned_words.db");
-    let mut banned_words = global_db.get_all();
-    let local_banned_words = local_db.get_all();
-    for w in local_banned_words.iter() {
-        banned_words.push(w.clone());
+/// Fetches the bot's own user from the cache, used as the moderator for automated actions.
+pub async fn get_bot_user(ctx: &Context) -> Result<User, String> {
+    Ok(User::from(ctx.cache.current_user().await))
+}
+
+/// Fetches every DSC ban on a user that has not been withdrawn.
+pub async fn get_active_dbans(pg_pool: &PgPool, user: UserId) -> Result<Vec<Dban>, String> {
+    sqlx::query_as!(
+        Dban,
+        "SELECT id,reason,guild_id,userid,is_withdrawn FROM dbans WHERE userid = $1 AND is_withdrawn = 'f' ORDER BY id",
+        user.as_u64().to_string()
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Number of distinct member servers the bans came from.
+pub fn count_ban_sources(bans: &[Dban]) -> usize {
+    bans.iter()
+        .map(|b| b.guild_id.as_str())
+        .collect::<HashSet<&str>>()
+        .len()
+}
+
+/// Lists each ban as "Server: Reason", trimmed to fit in an embed field.
+pub async fn describe_dbans(ctx: &Context, bans: &[Dban]) -> String {
+    let mut description = String::new();
+    for ban in bans.iter() {
+        let guild_name = match ban.guild_id.parse::<u64>() {
+            Ok(id) => match ctx.http.get_guild(id).await {
+                Ok(g) => g.name,
+                Err(_) => ban.guild_id.clone(),
+            },
+            Err(_) => ban.guild_id.clone(),
+        };
+        let line = format!("**{}**: {} (Case #{})\n", guild_name, ban.reason, ban.id);
+        if description.len() + line.len() > 1000 {
+            description.push_str("...");
+            break;
+        }
+        description.push_str(&line);
     }
 
-    let mut lower_content = content.to_lowercase();
+    description
+}
 
-    for w in banned_words.iter() {
-        if lower_content.contains(w) {
-            return true;
-        } else {
-            continue;
+/// Applies a guild's `on_banned_join` setting to a member who has an active DSC ban.
+/// Returns the action taken, or `None` if the guild only wants an alert.
+pub async fn act_on_banned_join(
+    ctx: &Context,
+    guild: GuildId,
+    member: &Member,
+    reason: &str,
+) -> Result<Option<&'static str>, String> {
+    let settings = get_guild_settings(ctx, guild).await?;
+    let mode = settings
+        .get::<String>("on_banned_join")
+        .unwrap_or_else(|| String::from("alert"));
+
+    let (action_type, action_name) = match mode.as_str() {
+        "quarantine" => {
+            let quarantine_role = match settings.get::<u64>("quarantine_role") {
+                Some(r) if r != 0 => RoleId(r),
+                _ => return Err(String::from("Quarantine role is not configured")),
+            };
+            let mut member = member.clone();
+            member
+                .add_role(&ctx.http, quarantine_role)
+                .await
+                .map_err(|e| e.to_string())?;
+            (ModActionType::Quarantine, "Quarantined")
+        }
+        "kick" => {
+            guild
+                .kick(&ctx.http, member.user.id)
+                .await
+                .map_err(|e| e.to_string())?;
+            (ModActionType::Kick, "Kicked")
+        }
+        "ban" => {
+            guild
+                .ban_with_reason(
+                    &ctx.http,
+                    member.user.id,
+                    0,
+                    &format!("{} {}", AUTO_BAN_PREFIX, reason),
+                )
+                .await
+                .map_err(|e| e.to_string())?;
+            (ModActionType::Ban, "Banned")
         }
+        _ => return Ok(None),
+    };
+
+    let action = ModAction {
+        target: Some(member.user.id),
+        moderator: get_bot_user(ctx).await?,
+        action_type,
+        reason: Some(reason.to_string()),
+        details: Some(String::from("User is on the DSC banlist")),
+        guild,
+        case_id: None,
+        old_value: None,
+        new_value: None,
+    };
+    log_mod_action(action, ctx).await;
+
+    Ok(Some(action_name))
+}
+
+/// Checks a user's active strikes in a guild against the guild's escalation settings, and
+/// applies the most severe consequence whose threshold has been reached. Active strikes record
+/// the consequence they led to, so it isn't applied twice while they still count.
+pub async fn escalate_strikes(
+    ctx: &Context,
+    pg_pool: &PgPool,
+    guild: GuildId,
+    target: UserId,
+) -> Result<(), String> {
+    let active_strikes = sqlx::query!(
+        "SELECT COUNT(*) AS count FROM strikes WHERE userid = $1 AND guild_id = $2 AND is_withdrawn = 'f' AND (expires_at IS NULL OR expires_at > NOW())",
+        target.as_u64().to_string(),
+        guild.as_u64().to_string()
+    )
+    .fetch_one(pg_pool)
+    .await
+    .map_err(|e| e.to_string())?
+    .count
+    .unwrap_or(0) as u64;
+
+    let settings = GuildSettings::load(pg_pool, guild).await?;
+    let reached = |key: &str| match settings.get::<u64>(key) {
+        Some(threshold) => threshold != 0 && threshold <= active_strikes,
+        None => false,
+    };
+
+    // Levels match the `escalation_level` column on strikes
+    let (action_type, level) = if reached("escalation_ban_strikes") {
+        (ModActionType::Ban, 3)
+    } else if reached("escalation_kick_strikes") {
+        (ModActionType::Kick, 2)
+    } else if reached("escalation_timeout_strikes") {
+        (ModActionType::Timeout, 1)
+    } else {
+        return Ok(());
+    };
+
+    // Marks the active strikes with this level, unless they already led to it or something
+    // harsher. Rolled back if the consequence can't be applied.
+    let mut tx = pg_pool.begin().await.map_err(|e| e.to_string())?;
+    let claimed = sqlx::query!(
+        "UPDATE strikes SET escalation_level = $3 WHERE userid = $1 AND guild_id = $2 AND is_withdrawn = 'f' AND (expires_at IS NULL OR expires_at > NOW()) AND NOT EXISTS (SELECT 1 FROM strikes WHERE userid = $1 AND guild_id = $2 AND is_withdrawn = 'f' AND (expires_at IS NULL OR expires_at > NOW()) AND escalation_level >= $3)",
+        target.as_u64().to_string(),
+        guild.as_u64().to_string(),
+        level
+    )
+    .execute(&mut tx)
+    .await
+    .map_err(|e| e.to_string())?;
+    if claimed == 0 {
+        return Ok(());
     }
-    return false;
+
+    let reason = format!("Reached {} active strikes", active_strikes);
+    match action_type {
+        ModActionType::Ban => {
+            guild
+                .ban_with_reason(&ctx.http, target, 0, &reason)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        ModActionType::Kick => {
+            guild
+                .kick(&ctx.http, target)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+        _ => {
+            let timeout_role = match settings.get::<u64>("timeout_role") {
+                Some(r) if r != 0 => RoleId(r),
+                _ => return Err(String::from("Timeout role is not configured")),
+            };
+            let mut member = guild
+                .member(ctx, target)
+                .await
+                .map_err(|e| e.to_string())?;
+            member
+                .add_role(&ctx.http, timeout_role)
+                .await
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    let action = ModAction {
+        target: Some(target),
+        moderator: get_bot_user(ctx).await?,
+        action_type,
+        reason: Some(reason),
+        details: Some(String::from("Automatic strike escalation")),
+        guild,
+        case_id: None,
+        old_value: None,
+        new_value: None,
+    };
+    log_mod_action(action, ctx).await;
+
+    Ok(())
 }
 
+/// Records the action in the `mod_actions` table, then posts it to the guild's mod log
+/// channel if one is configured.
 pub async fn log_mod_action<'fut>(action: ModAction, ctx: &'fut Context) {
     let guild_id = &action.guild;
-    let settings = get_pickle_database(guild_id.as_u64(), "settings.db");
-    let mod_log_channel: ChannelId = settings.get::<u64>("modlogs_channel").unwrap().into();
 
-    let target_name: &String = &action.target.to_user(&ctx.http).await.unwrap().name;
+    let log_id = {
+        let bot_data = ctx.data.read().await;
+        let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+        match sqlx::query!(
+            "INSERT INTO mod_actions (guild_id, target, moderator, action_type, reason, details, case_id, old_value, new_value) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id",
+            guild_id.as_u64().to_string(),
+            action.target.map(|t| t.as_u64().to_string()),
+            action.moderator.id.as_u64().to_string(),
+            action.action_type.key(),
+            action.reason,
+            action.details,
+            action.case_id,
+            action.old_value,
+            action.new_value
+        )
+        .fetch_one(pg_pool)
+        .await
+        {
+            Ok(r) => Some(r.id),
+            Err(err) => {
+                error!(
+                    "Error saving mod log entry for {}: {:?}",
+                    guild_id.as_u64(),
+                    err
+                );
+                None
+            }
+        }
+    };
 
-    mod_log_channel
+    let settings = match get_guild_settings(ctx, *guild_id).await {
+        Ok(s) => s,
+        Err(err) => {
+            error!("Error loading settings for {}: {}", guild_id.as_u64(), err);
+            return;
+        }
+    };
+    let mod_log_channel: ChannelId = match settings.get::<u64>("modlogs_channel") {
+        Some(c) if c != 0 => c.into(),
+        _ => {
+            debug!("No mod logs channel set for {}", guild_id.as_u64());
+            return;
+        }
+    };
+
+    let target_name = match action.target {
+        Some(target) => match target.to_user(&ctx.http).await {
+            Ok(u) => format!("{}#{}", u.name, u.discriminator),
+            Err(_) => target.as_u64().to_string(),
+        },
+        None => String::from("N/A"),
+    };
+
+    if let Err(err) = mod_log_channel
         .send_message(&ctx, |m| {
             m.embed(|e| {
                 e.title("Moderation Log Entry");
                 e.fields(vec![
                     ("User", &target_name, true),
-                    ("Moderator", &&action.moderator.name, true),
+                    ("Moderator", &action.moderator.name, true),
                 ]);
 
+                if let Some(c) = &action.case_id {
+                    e.field("Case", format!("#{}", c), true);
+                }
+
                 if let Some(r) = &action.reason {
-                    e.field("Reason", r, true);
+                    e.field("Reason", truncate_field(r), true);
                 } else {
                     e.field("Reason", "No reason provided", true);
                 }
 
-                if let Some(d) = &action.reason {
-                    e.field("Details", d, true);
+                if let Some(d) = &action.details {
+                    e.field("Details", truncate_field(d), true);
                 }
 
-                match &action.action_type {
-                    ModActionType::Strike => {
-                        e.field("Type", "Strike", false);
-                    }
-                    ModActionType::BadWordDelete => {
-                        e.field("Type", "Word Filter", false);
-                    }
-                    ModActionType::ClearStrikes => {
-                        e.field("Type", "Strikelog Clear", false);
-                    }
-                };
+                if let Some(o) = &action.old_value {
+                    e.field("Old Value", truncate_field(o), true);
+                }
+                if let Some(n) = &action.new_value {
+                    e.field("New Value", truncate_field(n), true);
+                }
+
+                e.field("Type", action.action_type.name(), false);
+
+                if let Some(id) = log_id {
+                    e.footer(|f| {
+                        f.text(format!("Log Entry #{}", id));
+                        f
+                    });
+                }
 
                 e
             });
@@ -89,5 +496,11 @@ pub async fn log_mod_action<'fut>(action: ModAction, ctx: &'fut Context) {
             m
         })
         .await
-        .unwrap();
+    {
+        error!(
+            "Error sending mod log entry to {}: {:?}",
+            guild_id.as_u64(),
+            err
+        );
+    }
 }
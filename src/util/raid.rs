@@ -0,0 +1,671 @@
+/*
+ *   Copyright (c) 2020 Owen Salter <owen@devosmium.xyz>
+ *   All rights reserved.
+ */
+
+use super::moderation::{get_bot_user, log_mod_action, ModAction, ModActionType};
+use super::settings::{get_guild_settings, GuildSettings};
+use crate::models::{Raid, RaidChannel};
+use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use serenity::model::{
+    channel::{ChannelType, PermissionOverwrite, PermissionOverwriteType, ReactionType},
+    id::{ChannelId, GuildId, RoleId},
+    permissions::Permissions,
+};
+use sqlx::PgPool;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Slowmode applied to every text channel while a raid is in progress
+const RAID_SLOW_MODE_RATE: u64 = 21600;
+
+/// How long raid mode lasts when it is enabled by raid detection
+const DETECTED_RAID_MINUTES: i64 = 60;
+
+/// How long moderators have to confirm a detected raid from the mod log
+const DETECTED_RAID_CONFIRM_SECS: u64 = 600;
+
+/// Set once raids have been resumed, so reconnects don't schedule their ends again
+static RAIDS_RESUMED: AtomicBool = AtomicBool::new(false);
+
+/// Valid values for the `raid_detect_action` guild setting.
+pub const RAID_DETECT_ACTIONS: [&str; 2] = ["alert", "enable"];
+
+// Recent joins from new accounts, per guild
+pub struct JoinTracker;
+impl TypeMapKey for JoinTracker {
+    type Value = Arc<Mutex<HashMap<GuildId, VecDeque<DateTime<Utc>>>>>;
+}
+
+pub struct RaidOptions {
+    pub lockdown: bool,
+    /// What happens to accounts joining during the raid: `none`, `kick` or `quarantine`
+    pub join_action: String,
+    pub duration: Option<chrono::Duration>,
+}
+
+pub async fn get_active_raid(pg_pool: &PgPool, guild: GuildId) -> Result<Option<Raid>, String> {
+    sqlx::query_as!(
+        Raid,
+        "SELECT guild_id,moderator,lockdown,join_action,started_at,ends_at FROM raids WHERE guild_id = $1",
+        guild.as_u64().to_string()
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Snapshots every text channel, applies raid slowmode (and the lockdown if requested),
+/// and records the raid so it survives a restart.
+pub async fn start_raid(
+    ctx: &Context,
+    pg_pool: &PgPool,
+    guild: GuildId,
+    moderator: UserId,
+    options: &RaidOptions,
+) -> Result<Option<DateTime<Utc>>, String> {
+    let ends_at = options.duration.map(|d| Utc::now() + d);
+    let everyone = RoleId(*guild.as_u64());
+    let mut guild_channels = guild.channels(&ctx.http).await.map_err(|e| e.to_string())?;
+
+    let mut tx = pg_pool.begin().await.map_err(|e| e.to_string())?;
+    sqlx::query!(
+        "INSERT INTO raids (guild_id, moderator, lockdown, join_action, ends_at) VALUES ($1, $2, $3, $4, $5)",
+        guild.as_u64().to_string(),
+        moderator.as_u64().to_string(),
+        options.lockdown,
+        options.join_action,
+        ends_at
+    )
+    .execute(&mut tx)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    for (id, gc) in guild_channels.iter() {
+        if gc.kind != ChannelType::Text {
+            continue;
+        }
+        let everyone_overwrite = gc
+            .permission_overwrites
+            .iter()
+            .find(|o| o.kind == PermissionOverwriteType::Role(everyone));
+        sqlx::query!(
+            "INSERT INTO raid_channels (guild_id, channel_id, slow_mode_rate, everyone_allow, everyone_deny) VALUES ($1, $2, $3, $4, $5)",
+            guild.as_u64().to_string(),
+            id.as_u64().to_string(),
+            gc.slow_mode_rate.unwrap_or(0) as i32,
+            everyone_overwrite.map(|o| o.allow.bits() as i64),
+            everyone_overwrite.map(|o| o.deny.bits() as i64)
+        )
+        .execute(&mut tx)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    for (id, gc) in guild_channels.iter_mut() {
+        if gc.kind != ChannelType::Text {
+            continue;
+        }
+        if let Err(e) = gc
+            .edit(&ctx.http, |e| {
+                e.slow_mode_rate(RAID_SLOW_MODE_RATE);
+                e
+            })
+            .await
+        {
+            error!(
+                "Error in raid: Could not set slowmode on channel {}: {:?}",
+                id.as_u64().to_string(),
+                e
+            );
+        }
+
+        if options.lockdown {
+            let (allow, deny) = match gc
+                .permission_overwrites
+                .iter()
+                .find(|o| o.kind == PermissionOverwriteType::Role(everyone))
+            {
+                Some(o) => (o.allow, o.deny),
+                None => (Permissions::empty(), Permissions::empty()),
+            };
+            let overwrite = PermissionOverwrite {
+                allow: allow - Permissions::SEND_MESSAGES,
+                deny: deny | Permissions::SEND_MESSAGES,
+                kind: PermissionOverwriteType::Role(everyone),
+            };
+            if let Err(e) = gc.create_permission(&ctx.http, &overwrite).await {
+                error!(
+                    "Error in raid: Could not lock channel {}: {:?}",
+                    id.as_u64().to_string(),
+                    e
+                );
+            }
+        }
+    }
+
+    if let Some(t) = ends_at {
+        schedule_raid_end(ctx.clone(), guild, t);
+    }
+
+    let mut details = format!("Lockdown: {}, New joins: {}", options.lockdown, options.join_action);
+    if let Some(t) = ends_at {
+        details.push_str(&format!(", Lifts at {}", t.format("%Y-%m-%d %H:%MZ")));
+    }
+    let action = ModAction {
+        target: None,
+        moderator: moderator.to_user(ctx).await.map_err(|e| e.to_string())?,
+        action_type: ModActionType::Raid,
+        reason: None,
+        details: Some(details),
+        guild,
+        case_id: None,
+        old_value: None,
+        new_value: None,
+    };
+    log_mod_action(action, ctx).await;
+
+    Ok(ends_at)
+}
+
+/// Restores every channel from its snapshot and clears the raid.
+/// Returns false if the guild was not in raid mode.
+pub async fn end_raid(
+    ctx: &Context,
+    pg_pool: &PgPool,
+    guild: GuildId,
+    moderator: UserId,
+) -> Result<bool, String> {
+    let raid = match get_active_raid(pg_pool, guild).await? {
+        Some(r) => r,
+        None => return Ok(false),
+    };
+    let everyone = RoleId(*guild.as_u64());
+    let snapshots = sqlx::query_as!(
+        RaidChannel,
+        "SELECT channel_id,slow_mode_rate,everyone_allow,everyone_deny FROM raid_channels WHERE guild_id = $1",
+        guild.as_u64().to_string()
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    let mut guild_channels = guild.channels(&ctx.http).await.map_err(|e| e.to_string())?;
+
+    for snapshot in snapshots.iter() {
+        let channel_id = match snapshot.channel_id.parse::<u64>() {
+            Ok(id) => ChannelId(id),
+            Err(_) => continue,
+        };
+        // Channels deleted during the raid have nothing to restore
+        let gc = match guild_channels.get_mut(&channel_id) {
+            Some(c) => c,
+            None => continue,
+        };
+
+        if let Err(e) = gc
+            .edit(&ctx.http, |e| {
+                e.slow_mode_rate(snapshot.slow_mode_rate as u64);
+                e
+            })
+            .await
+        {
+            error!(
+                "Error in unraid: Could not restore slowmode on channel {}: {:?}",
+                snapshot.channel_id, e
+            );
+        }
+
+        if raid.lockdown {
+            let result = match (snapshot.everyone_allow, snapshot.everyone_deny) {
+                (Some(allow), Some(deny)) => {
+                    let overwrite = PermissionOverwrite {
+                        allow: Permissions::from_bits_truncate(allow as u64),
+                        deny: Permissions::from_bits_truncate(deny as u64),
+                        kind: PermissionOverwriteType::Role(everyone),
+                    };
+                    gc.create_permission(&ctx.http, &overwrite).await
+                }
+                _ => {
+                    gc.delete_permission(&ctx.http, PermissionOverwriteType::Role(everyone))
+                        .await
+                }
+            };
+            if let Err(e) = result {
+                error!(
+                    "Error in unraid: Could not unlock channel {}: {:?}",
+                    snapshot.channel_id, e
+                );
+            }
+        }
+    }
+
+    sqlx::query!(
+        "DELETE FROM raids WHERE guild_id = $1",
+        guild.as_u64().to_string()
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let action = ModAction {
+        target: None,
+        moderator: moderator.to_user(ctx).await.map_err(|e| e.to_string())?,
+        action_type: ModActionType::Unraid,
+        reason: None,
+        details: None,
+        guild,
+        case_id: None,
+        old_value: None,
+        new_value: None,
+    };
+    log_mod_action(action, ctx).await;
+
+    Ok(true)
+}
+
+/// Ends the raid once `ends_at` passes, unless it was already lifted or restarted with a
+/// different end time.
+pub fn schedule_raid_end(ctx: Context, guild: GuildId, ends_at: DateTime<Utc>) {
+    tokio::spawn(async move {
+        let wait = (ends_at - Utc::now())
+            .to_std()
+            .unwrap_or(Duration::from_secs(0));
+        tokio::time::delay_for(wait).await;
+
+        let pg_pool = {
+            let bot_data = ctx.data.read().await;
+            bot_data.get::<ConnectionPool>().unwrap().clone()
+        };
+        let still_scheduled = match get_active_raid(&pg_pool, guild).await {
+            Ok(Some(raid)) => raid.ends_at.map(|t| t.timestamp()) == Some(ends_at.timestamp()),
+            Ok(None) => false,
+            Err(err) => {
+                error!("Error checking raid state for {}: {}", guild.as_u64(), err);
+                false
+            }
+        };
+        if !still_scheduled {
+            return;
+        }
+
+        let bot_id = match get_bot_user(&ctx).await {
+            Ok(u) => u.id,
+            Err(err) => {
+                error!("Could not fetch bot user: {}", err);
+                return;
+            }
+        };
+        match end_raid(&ctx, &pg_pool, guild, bot_id).await {
+            Ok(_) => {
+                info!("Raid mode expired in {}", guild.as_u64());
+                let guild_name = match guild.to_partial_guild(&ctx.http).await {
+                    Ok(g) => g.name,
+                    Err(_) => guild.as_u64().to_string(),
+                };
+                if let Err(err) = notify_raid(&ctx, &guild_name, "Automatic", false).await {
+                    error!("Error alerting DSC to unraid in {}: {}", guild_name, err);
+                }
+            }
+            Err(err) => error!(
+                "Error lifting expired raid mode in {}: {}",
+                guild.as_u64(),
+                err
+            ),
+        }
+    });
+}
+
+/// Picks raids back up after a restart: expired ones are lifted, the rest are rescheduled.
+/// Only runs once, since `ready` fires again on every reconnect.
+pub async fn resume_raids(ctx: &Context) {
+    if RAIDS_RESUMED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    let pg_pool = {
+        let bot_data = ctx.data.read().await;
+        bot_data.get::<ConnectionPool>().unwrap().clone()
+    };
+    let raids = match sqlx::query_as!(
+        Raid,
+        "SELECT guild_id,moderator,lockdown,join_action,started_at,ends_at FROM raids"
+    )
+    .fetch_all(&pg_pool)
+    .await
+    {
+        Ok(r) => r,
+        Err(err) => {
+            error!("Error fetching active raids: {:?}", err);
+            return;
+        }
+    };
+
+    for raid in raids {
+        let guild = match raid.guild_id.parse::<u64>() {
+            Ok(id) => GuildId(id),
+            Err(_) => continue,
+        };
+        if let Some(ends_at) = raid.ends_at {
+            schedule_raid_end(ctx.clone(), guild, ends_at);
+        }
+    }
+}
+
+/// Applies the raid's join action to a member who joined while raid mode is active.
+pub async fn gate_raid_join(
+    ctx: &Context,
+    pg_pool: &PgPool,
+    guild: GuildId,
+    member: &Member,
+) -> Result<(), String> {
+    let raid = match get_active_raid(pg_pool, guild).await? {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+
+    let action_type = match raid.join_action.as_str() {
+        "kick" => {
+            guild
+                .kick(&ctx.http, member.user.id)
+                .await
+                .map_err(|e| e.to_string())?;
+            ModActionType::Kick
+        }
+        "quarantine" => {
+            let settings = get_guild_settings(ctx, guild).await?;
+            let quarantine_role = match settings.get::<u64>("quarantine_role") {
+                Some(r) if r != 0 => RoleId(r),
+                _ => return Err(String::from("Quarantine role is not configured")),
+            };
+            let mut member = member.clone();
+            member
+                .add_role(&ctx.http, quarantine_role)
+                .await
+                .map_err(|e| e.to_string())?;
+            ModActionType::Quarantine
+        }
+        _ => return Ok(()),
+    };
+
+    let action = ModAction {
+        target: Some(member.user.id),
+        moderator: get_bot_user(ctx).await?,
+        action_type,
+        reason: Some(String::from("Joined while raid mode was active")),
+        details: None,
+        guild,
+        case_id: None,
+        old_value: None,
+        new_value: None,
+    };
+    log_mod_action(action, ctx).await;
+
+    Ok(())
+}
+
+/// Alerts DSC that a server has entered or left raid mode.
+pub async fn notify_raid(
+    ctx: &Context,
+    guild_name: &str,
+    moderator: &str,
+    started: bool,
+) -> Result<(), String> {
+    let channel = ctx
+        .http
+        .get_channel(crate::prelude::NOTIFY_CHANNEL)
+        .await
+        .map_err(|e| e.to_string())?;
+    channel
+        .id()
+        .send_message(&ctx, |m| {
+            if started {
+                m.content("@everyone");
+            }
+            m.embed(|e| {
+                if started {
+                    e.title("RAID IN PROGRESS");
+                    e.colour(Colour::RED);
+                } else {
+                    e.title("Raid Mode Lifted");
+                    e.colour(Colour::BLUE);
+                }
+                e.fields(vec![
+                    ("Server", guild_name, true),
+                    ("Moderator", moderator, true),
+                ]);
+                e.footer(|f| {
+                    f.text("DSC Bot | Powered by Rusty Development");
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Records a join and checks it against the guild's raid detection settings. When enough new
+/// accounts join inside the window, raid mode is either enabled or offered to the moderators.
+pub async fn track_join(
+    ctx: &Context,
+    pg_pool: &PgPool,
+    tracker: &Arc<Mutex<HashMap<GuildId, VecDeque<DateTime<Utc>>>>>,
+    guild: GuildId,
+    member: &Member,
+) -> Result<(), String> {
+    let settings = GuildSettings::load(pg_pool, guild).await?;
+    let threshold = settings.get::<u64>("raid_detect_joins").unwrap_or(0);
+    if threshold == 0 {
+        return Ok(());
+    }
+    let window = chrono::Duration::seconds(settings.get::<u64>("raid_detect_window").unwrap_or(60) as i64);
+    let max_age = settings.get::<u64>("raid_detect_account_age").unwrap_or(0);
+    // 0 counts every join, not just new accounts
+    if max_age != 0
+        && member.user.id.created_at() < Utc::now() - chrono::Duration::seconds(max_age as i64)
+    {
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let burst = {
+        let mut tracker = tracker.lock().await;
+        let joins = tracker.entry(guild).or_insert_with(VecDeque::new);
+        joins.push_back(now);
+        while let Some(t) = joins.front() {
+            if *t < now - window {
+                joins.pop_front();
+            } else {
+                break;
+            }
+        }
+        if joins.len() as u64 >= threshold {
+            let count = joins.len();
+            joins.clear();
+            Some(count)
+        } else {
+            None
+        }
+    };
+    let joins = match burst {
+        Some(count) => count,
+        None => return Ok(()),
+    };
+
+    if get_active_raid(pg_pool, guild).await?.is_some() {
+        return Ok(());
+    }
+
+    let guild_name = match guild.to_partial_guild(&ctx.http).await {
+        Ok(g) => g.name,
+        Err(_) => guild.as_u64().to_string(),
+    };
+    let summary = format!(
+        "{} new accounts joined in the last {} seconds",
+        joins,
+        window.num_seconds()
+    );
+    warn!("Possible raid detected in {}: {}", guild_name, summary);
+
+    let mod_log_channel: ChannelId = settings.get::<u64>("modlogs_channel").unwrap_or(0).into();
+    let enable = settings
+        .get::<String>("raid_detect_action")
+        .map(|a| a == "enable")
+        .unwrap_or(false);
+
+    if enable {
+        let bot_user = get_bot_user(ctx).await?;
+        let options = RaidOptions {
+            lockdown: false,
+            join_action: String::from("none"),
+            duration: Some(chrono::Duration::minutes(DETECTED_RAID_MINUTES)),
+        };
+        start_raid(ctx, pg_pool, guild, bot_user.id, &options).await?;
+        notify_raid(ctx, &guild_name, "Automatic detection", true).await?;
+        if *mod_log_channel.as_u64() != 0 {
+            if let Err(err) = mod_log_channel
+                .send_message(&ctx, |m| {
+                    m.embed(|e| {
+                        e.title("Raid Detected");
+                        e.description(format!(
+                            "{}. Raid mode has been enabled for {} minutes; use `unraid` to lift it early.",
+                            summary, DETECTED_RAID_MINUTES
+                        ));
+                        e.colour(Colour::RED);
+                        e
+                    });
+                    m
+                })
+                .await
+            {
+                error!("Error sending raid detection alert: {:?}", err);
+            }
+        }
+        return Ok(());
+    }
+
+    if let Ok(c) = ctx.http.get_channel(crate::prelude::NOTIFY_CHANNEL).await {
+        if let Err(err) = c
+            .id()
+            .send_message(&ctx, |m| {
+                m.embed(|e| {
+                    e.title("Possible Raid Detected");
+                    e.fields(vec![("Server", &guild_name, true), ("Joins", &summary, true)]);
+                    e.colour(Colour::ORANGE);
+                    e
+                });
+                m
+            })
+            .await
+        {
+            error!("Error alerting DSC to possible raid in {}: {:?}", guild_name, err);
+        }
+    }
+
+    if *mod_log_channel.as_u64() == 0 {
+        return Err(String::from("Mod logs channel is not set"));
+    }
+    let alert = mod_log_channel
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Possible Raid Detected");
+                e.description(format!(
+                    "{}. A moderator can react with 🚨 to enable raid mode.",
+                    summary
+                ));
+                e.colour(Colour::ORANGE);
+                e
+            });
+            m
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+    alert
+        .react(&ctx.http, '🚨')
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let ctx = ctx.clone();
+    let pg_pool = pg_pool.clone();
+    tokio::spawn(async move {
+        let bot_id = match get_bot_user(&ctx).await {
+            Ok(u) => u.id,
+            Err(err) => {
+                error!("Could not fetch bot user: {}", err);
+                return;
+            }
+        };
+        while let Some(action) = alert
+            .await_reaction(&ctx)
+            .timeout(Duration::from_secs(DETECTED_RAID_CONFIRM_SECS))
+            .await
+        {
+            let reaction = action.as_inner_ref();
+            if reaction.user_id == bot_id || reaction.emoji != ReactionType::Unicode("🚨".to_string()) {
+                continue;
+            }
+            let is_moderator = match guild.member(&ctx, reaction.user_id).await {
+                Ok(m) => match m.permissions(&ctx.cache).await {
+                    Ok(p) => p.manage_guild(),
+                    Err(_) => false,
+                },
+                Err(_) => false,
+            };
+            if !is_moderator {
+                continue;
+            }
+
+            match get_active_raid(&pg_pool, guild).await {
+                Ok(None) => (),
+                Ok(Some(_)) => {
+                    if let Err(err) = mod_log_channel
+                        .say(&ctx.http, "Raid mode is already enabled. Use `unraid` to lift it.")
+                        .await
+                    {
+                        error!("Error confirming raid mode: {:?}", err);
+                    }
+                    break;
+                }
+                Err(err) => {
+                    error!("Error checking raid state for {}: {}", guild_name, err);
+                    break;
+                }
+            }
+            let options = RaidOptions {
+                lockdown: false,
+                join_action: String::from("none"),
+                duration: None,
+            };
+            match start_raid(&ctx, &pg_pool, guild, reaction.user_id, &options).await {
+                Ok(_) => {
+                    if let Err(err) = notify_raid(
+                        &ctx,
+                        &guild_name,
+                        &format!("<@{}>", reaction.user_id.as_u64()),
+                        true,
+                    )
+                    .await
+                    {
+                        error!("Error alerting DSC to raid in {}: {}", guild_name, err);
+                    }
+                    if let Err(err) = mod_log_channel
+                        .say(&ctx.http, "Raid mode enabled. Use `unraid` to lift it.")
+                        .await
+                    {
+                        error!("Error confirming raid mode: {:?}", err);
+                    }
+                }
+                Err(err) => error!("Error enabling raid mode in {}: {}", guild_name, err),
+            }
+            break;
+        }
+    });
+
+    Ok(())
+}
@@ -0,0 +1,418 @@
+/*
+ *   Copyright (c) 2020 Owen Salter <owen@devosmium.xyz>
+ *   All rights reserved.
+ */
+
+use super::moderation::BANNED_JOIN_MODES;
+use super::{parse_duration, MAX_DURATION_SECS};
+use super::raid::RAID_DETECT_ACTIONS;
+use super::spam::SPAM_ACTIONS;
+use super::verification::{award_role_setting, get_verification_types};
+use super::wordfilter::{NAME_FILTER_ACTIONS, WORD_FILTER_ACTIONS};
+use crate::models::VerificationType;
+use crate::ConnectionPool;
+use serenity::client::Context;
+use serenity::model::id::{ChannelId, GuildId, RoleId};
+use sqlx::PgPool;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Clone, Copy)]
+pub enum SettingType {
+    /// Stored as the channel ID, 0 when unset
+    Channel,
+    /// Stored as the role ID, 0 when unset
+    Role,
+    /// Stored as 1 or 0
+    Bool,
+    Integer,
+    /// Stored in seconds
+    Duration,
+    /// Free text, or one of a fixed set of choices
+    String(Option<&'static [&'static str]>),
+}
+
+impl SettingType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SettingType::Channel => "channel",
+            SettingType::Role => "role",
+            SettingType::Bool => "bool",
+            SettingType::Integer => "integer",
+            SettingType::Duration => "duration",
+            SettingType::String(_) => "string",
+        }
+    }
+
+    /// Describes the values the setting accepts, for help and error messages.
+    pub fn expected(&self) -> String {
+        match self {
+            SettingType::Channel => String::from("a #channel mention or ID, or `none`"),
+            SettingType::Role => String::from("a @role mention or ID, or `none`"),
+            SettingType::Bool => String::from("`on` or `off`"),
+            SettingType::Integer => String::from("a whole number"),
+            SettingType::Duration => String::from("a duration such as `30s`, `10m` or `1h`"),
+            SettingType::String(Some(choices)) => format!("one of: {}", choices.join(", ")),
+            SettingType::String(None) => String::from("any text"),
+        }
+    }
+}
+
+pub enum SettingValue {
+    Number(u64),
+    Text(String),
+}
+
+impl SettingValue {
+    /// The value as it is stored in the `guild_settings` table.
+    pub fn to_stored(&self) -> String {
+        match self {
+            SettingValue::Number(n) => n.to_string(),
+            SettingValue::Text(t) => t.clone(),
+        }
+    }
+}
+
+/// A guild's settings, loaded from the `guild_settings` table. Settings the guild has never
+/// changed read as their registered default.
+pub struct GuildSettings {
+    guild: GuildId,
+    values: HashMap<String, String>,
+}
+
+impl GuildSettings {
+    pub async fn load(pg_pool: &PgPool, guild: GuildId) -> Result<GuildSettings, String> {
+        let rows = sqlx::query!(
+            "SELECT name,value FROM guild_settings WHERE guild_id = $1",
+            guild.as_u64().to_string()
+        )
+        .fetch_all(pg_pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        Ok(GuildSettings {
+            guild,
+            values: rows.into_iter().map(|r| (r.name, r.value)).collect(),
+        })
+    }
+
+    fn raw(&self, key: &str) -> Option<String> {
+        match self.values.get(key) {
+            Some(v) => Some(v.clone()),
+            None => find_setting(key).map(|s| s.default_value().to_stored()),
+        }
+    }
+
+    /// Reads a setting as `T`, e.g. `u64` for IDs, counts and durations or `String` for text.
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.raw(key).and_then(|v| v.parse::<T>().ok())
+    }
+
+    pub async fn set(&mut self, pg_pool: &PgPool, key: &str, value: &SettingValue) -> Result<(), String> {
+        let value = value.to_stored();
+        sqlx::query!(
+            "INSERT INTO guild_settings (guild_id, name, value) VALUES ($1, $2, $3) ON CONFLICT (guild_id, name) DO UPDATE SET value = EXCLUDED.value",
+            self.guild.as_u64().to_string(),
+            key,
+            value
+        )
+        .execute(pg_pool)
+        .await
+        .map_err(|e| e.to_string())?;
+        self.values.insert(key.to_string(), value);
+
+        Ok(())
+    }
+}
+
+/// Loads a guild's settings using the client's connection pool.
+pub async fn get_guild_settings(ctx: &Context, guild: GuildId) -> Result<GuildSettings, String> {
+    let data = ctx.data.read().await;
+    let pg_pool = data.get::<ConnectionPool>().unwrap();
+
+    GuildSettings::load(pg_pool, guild).await
+}
+
+#[derive(Clone)]
+pub struct Setting {
+    pub name: Cow<'static, str>,
+    pub setting_type: SettingType,
+    /// Default value, written the way `serversettings set` accepts it
+    pub default: &'static str,
+    pub description: Cow<'static, str>,
+}
+
+impl Setting {
+    /// Parses and validates a value given to `serversettings set`.
+    pub fn parse(&self, input: &str) -> Result<SettingValue, String> {
+        let input = input.trim();
+        let invalid = || {
+            format!(
+                "Invalid value for {}. Expected {}",
+                self.name,
+                self.setting_type.expected()
+            )
+        };
+
+        match self.setting_type {
+            SettingType::Channel | SettingType::Role if input == "none" || input == "0" => {
+                Ok(SettingValue::Number(0))
+            }
+            SettingType::Channel => input
+                .parse::<ChannelId>()
+                .map(|c| SettingValue::Number(*c.as_u64()))
+                .map_err(|_| invalid()),
+            SettingType::Role => input
+                .parse::<RoleId>()
+                .map(|r| SettingValue::Number(*r.as_u64()))
+                .map_err(|_| invalid()),
+            SettingType::Bool => match input.to_lowercase().as_str() {
+                "on" | "true" | "yes" | "1" => Ok(SettingValue::Number(1)),
+                "off" | "false" | "no" | "0" => Ok(SettingValue::Number(0)),
+                _ => Err(invalid()),
+            },
+            SettingType::Integer => input
+                .parse::<u64>()
+                .map(SettingValue::Number)
+                .map_err(|_| invalid()),
+            SettingType::Duration => match input.parse::<u64>() {
+                // A bare number is taken as seconds
+                Ok(secs) if secs <= MAX_DURATION_SECS as u64 => Ok(SettingValue::Number(secs)),
+                _ => parse_duration(input)
+                    .map(|d| SettingValue::Number(d.num_seconds() as u64))
+                    .ok_or_else(invalid),
+            },
+            SettingType::String(Some(choices)) => {
+                let choice = input.to_lowercase();
+                if choices.contains(&choice.as_str()) {
+                    Ok(SettingValue::Text(choice))
+                } else {
+                    Err(invalid())
+                }
+            }
+            SettingType::String(None) => Ok(SettingValue::Text(input.to_string())),
+        }
+    }
+
+    pub fn default_value(&self) -> SettingValue {
+        self.parse(self.default)
+            .unwrap_or_else(|e| panic!("Bad default for setting {}: {}", self.name, e))
+    }
+
+    /// Reads the setting's current value, falling back to the default.
+    pub fn get(&self, settings: &GuildSettings) -> SettingValue {
+        let value = match self.setting_type {
+            SettingType::String(_) => settings.get::<String>(&self.name).map(SettingValue::Text),
+            _ => settings.get::<u64>(&self.name).map(SettingValue::Number),
+        };
+
+        value.unwrap_or_else(|| self.default_value())
+    }
+
+    /// Formats a value for display in an embed.
+    pub fn display(&self, value: &SettingValue) -> String {
+        match (self.setting_type, value) {
+            (SettingType::Channel, SettingValue::Number(0))
+            | (SettingType::Role, SettingValue::Number(0)) => String::from("Not set"),
+            (SettingType::Channel, SettingValue::Number(c)) => format!("<#{}>", c),
+            (SettingType::Role, SettingValue::Number(r)) => format!("<@&{}>", r),
+            (SettingType::Bool, SettingValue::Number(0)) => String::from("off"),
+            (SettingType::Bool, SettingValue::Number(_)) => String::from("on"),
+            (SettingType::Duration, SettingValue::Number(s)) => format!("{}s", s),
+            (_, SettingValue::Number(n)) => n.to_string(),
+            (_, SettingValue::Text(t)) if t.is_empty() => String::from("‎"), // Contains a unicode "blank space" to appease JSON
+            (_, SettingValue::Text(t)) => t.clone(),
+        }
+    }
+}
+
+/// Every guild setting. Counts and limits of 0 disable the feature they control.
+pub static GUILD_SETTINGS: [Setting; 22] = [
+    Setting {
+        name: Cow::Borrowed("modlogs_channel"),
+        setting_type: SettingType::Channel,
+        default: "none",
+        description: Cow::Borrowed("Channel for the moderation log and banned user announcements"),
+    },
+    //* Strike escalation
+    Setting {
+        name: Cow::Borrowed("timeout_role"),
+        setting_type: SettingType::Role,
+        default: "none",
+        description: Cow::Borrowed("Role given to members placed in timeout"),
+    },
+    Setting {
+        name: Cow::Borrowed("escalation_timeout_strikes"),
+        setting_type: SettingType::Integer,
+        default: "0",
+        description: Cow::Borrowed("Active strikes before a member is placed in timeout"),
+    },
+    Setting {
+        name: Cow::Borrowed("escalation_kick_strikes"),
+        setting_type: SettingType::Integer,
+        default: "0",
+        description: Cow::Borrowed("Active strikes before a member is kicked"),
+    },
+    Setting {
+        name: Cow::Borrowed("escalation_ban_strikes"),
+        setting_type: SettingType::Integer,
+        default: "0",
+        description: Cow::Borrowed("Active strikes before a member is banned"),
+    },
+    //* Banned users joining
+    Setting {
+        name: Cow::Borrowed("on_banned_join"),
+        setting_type: SettingType::String(Some(&BANNED_JOIN_MODES)),
+        default: "alert",
+        description: Cow::Borrowed("What to do when a user on the DSC banlist joins"),
+    },
+    Setting {
+        name: Cow::Borrowed("quarantine_role"),
+        setting_type: SettingType::Role,
+        default: "none",
+        description: Cow::Borrowed("Role given to quarantined members"),
+    },
+    Setting {
+        name: Cow::Borrowed("banned_join_min_servers"),
+        setting_type: SettingType::Integer,
+        default: "1",
+        description: Cow::Borrowed("Servers that must have banned a user before acting on their join"),
+    },
+    //* Raid detection
+    Setting {
+        name: Cow::Borrowed("raid_detect_joins"),
+        setting_type: SettingType::Integer,
+        default: "0",
+        description: Cow::Borrowed("Joins from new accounts within the window that count as a raid"),
+    },
+    Setting {
+        name: Cow::Borrowed("raid_detect_window"),
+        setting_type: SettingType::Duration,
+        default: "60s",
+        description: Cow::Borrowed("Window that raid detection counts joins over"),
+    },
+    Setting {
+        name: Cow::Borrowed("raid_detect_account_age"),
+        setting_type: SettingType::Duration,
+        default: "7d",
+        description: Cow::Borrowed("Accounts younger than this count towards raid detection (0 counts every join)"),
+    },
+    Setting {
+        name: Cow::Borrowed("raid_detect_action"),
+        setting_type: SettingType::String(Some(&RAID_DETECT_ACTIONS)),
+        default: "alert",
+        description: Cow::Borrowed("Whether a detected raid alerts moderators or enables raid mode"),
+    },
+    //* Word filter
+    Setting {
+        name: Cow::Borrowed("word_filter_action"),
+        setting_type: SettingType::String(Some(&WORD_FILTER_ACTIONS)),
+        default: "warn",
+        description: Cow::Borrowed("What happens to members whose message is filtered"),
+    },
+    Setting {
+        name: Cow::Borrowed("word_filter_dm"),
+        setting_type: SettingType::Bool,
+        default: "off",
+        description: Cow::Borrowed("DM filtered members their removed message instead of warning them in the channel"),
+    },
+    Setting {
+        name: Cow::Borrowed("word_filter_nickname_action"),
+        setting_type: SettingType::String(Some(&NAME_FILTER_ACTIONS)),
+        default: "reset",
+        description: Cow::Borrowed("What to do when a member sets a filtered nickname"),
+    },
+    Setting {
+        name: Cow::Borrowed("word_filter_username_action"),
+        setting_type: SettingType::String(Some(&NAME_FILTER_ACTIONS)),
+        default: "alert",
+        description: Cow::Borrowed("What to do when a member with a filtered username joins"),
+    },
+    //* Spam detection
+    Setting {
+        name: Cow::Borrowed("spam_duplicate_messages"),
+        setting_type: SettingType::Integer,
+        default: "0",
+        description: Cow::Borrowed("Identical messages within the window that count as spam"),
+    },
+    Setting {
+        name: Cow::Borrowed("spam_rapid_messages"),
+        setting_type: SettingType::Integer,
+        default: "0",
+        description: Cow::Borrowed("Messages within the window that count as rapid posting"),
+    },
+    Setting {
+        name: Cow::Borrowed("spam_window"),
+        setting_type: SettingType::Duration,
+        default: "10s",
+        description: Cow::Borrowed("Window that spam detection counts messages over"),
+    },
+    Setting {
+        name: Cow::Borrowed("spam_mention_limit"),
+        setting_type: SettingType::Integer,
+        default: "0",
+        description: Cow::Borrowed("Mentions in a single message that count as spam"),
+    },
+    Setting {
+        name: Cow::Borrowed("spam_block_invites"),
+        setting_type: SettingType::Bool,
+        default: "off",
+        description: Cow::Borrowed("Remove invites to servers that aren't DSC members"),
+    },
+    Setting {
+        name: Cow::Borrowed("spam_action"),
+        setting_type: SettingType::String(Some(&SPAM_ACTIONS)),
+        default: "warn",
+        description: Cow::Borrowed("What happens to members caught spamming"),
+    },
+];
+
+pub fn find_setting(name: &str) -> Option<&'static Setting> {
+    GUILD_SETTINGS.iter().find(|s| s.name == name)
+}
+
+/// The role setting for a verification type, named `verified_role_<key>`.
+pub fn verified_role_setting(award: &VerificationType) -> Setting {
+    Setting {
+        name: Cow::Owned(award_role_setting(award)),
+        setting_type: SettingType::Role,
+        default: "none",
+        description: Cow::Owned(format!("Role given to members with a verified {} award", award.name)),
+    }
+}
+
+/// Every guild setting, followed by a role setting for each verification type.
+pub async fn get_settings_registry(pg_pool: &PgPool) -> Result<Vec<Setting>, String> {
+    let mut registry = GUILD_SETTINGS.to_vec();
+    for award in get_verification_types(pg_pool, true).await? {
+        registry.push(verified_role_setting(&award));
+    }
+
+    Ok(registry)
+}
+
+/// Finds a setting by name, including the verification type role settings.
+pub async fn lookup_setting(pg_pool: &PgPool, name: &str) -> Result<Option<Setting>, String> {
+    if let Some(setting) = find_setting(name) {
+        return Ok(Some(setting.clone()));
+    }
+
+    Ok(get_settings_registry(pg_pool)
+        .await?
+        .into_iter()
+        .find(|s| s.name == name))
+}
+
+/// Resets every setting in the guild to its default.
+pub async fn init_guild_settings(pg_pool: &PgPool, guild: GuildId) -> Result<(), String> {
+    sqlx::query!(
+        "DELETE FROM guild_settings WHERE guild_id = $1",
+        guild.as_u64().to_string()
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
@@ -0,0 +1,353 @@
+/*
+ *   Copyright (c) 2020 Owen Salter <owen@devosmium.xyz>
+ *   All rights reserved.
+ */
+
+use super::moderation::{get_bot_user, log_mod_action, ModAction, ModActionType};
+use super::settings::{get_guild_settings, GuildSettings};
+use super::wordfilter::is_exempt;
+use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use serenity::model::id::{GuildId, RoleId};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+
+/// Valid values for the `spam_action` guild setting.
+pub const SPAM_ACTIONS: [&str; 4] = ["delete", "warn", "timeout", "kick"];
+
+/// Links that point at a Discord server invite
+const INVITE_PREFIXES: [&str; 3] = ["discord.gg/", "discord.com/invite/", "discordapp.com/invite/"];
+
+/// Invite links looked up per message, so one message can't burn through the rate limit
+const MAX_INVITE_LOOKUPS: usize = 3;
+
+/// How long tracked messages and invite lookups are kept. Spam windows longer than this
+/// are cut short for members who stop posting.
+const TRACKER_RETENTION_SECS: i64 = 60 * 60;
+
+/// How often idle members and old invite lookups are swept out of the trackers
+const TRACKER_SWEEP_SECS: i64 = 5 * 60;
+
+/// When the trackers were last swept, as a Unix timestamp
+static LAST_SWEEP: AtomicI64 = AtomicI64::new(0);
+
+// Recent messages per member, as (sent at, content)
+pub struct MessageTracker;
+impl TypeMapKey for MessageTracker {
+    type Value = Arc<Mutex<HashMap<(GuildId, UserId), VecDeque<(DateTime<Utc>, String)>>>>;
+}
+
+// The server each looked up invite code points to, as (looked up at, server)
+pub struct InviteCache;
+impl TypeMapKey for InviteCache {
+    type Value = Arc<Mutex<HashMap<String, (DateTime<Utc>, Option<GuildId>)>>>;
+}
+
+enum SpamKind {
+    Duplicate(usize),
+    Rapid(usize),
+    Mentions(usize),
+    Invite(String),
+}
+
+impl SpamKind {
+    fn describe(&self) -> String {
+        match self {
+            SpamKind::Duplicate(n) => format!("Sent the same message {} times", n),
+            SpamKind::Rapid(n) => format!("Sent {} messages in quick succession", n),
+            SpamKind::Mentions(n) => format!("Mentioned {} users or roles in one message", n),
+            SpamKind::Invite(code) => format!("Posted an invite to a non-member server ({})", code),
+        }
+    }
+}
+
+/// Finds the invite codes in any Discord invite links in the message.
+fn find_invite_codes(content: &str) -> Vec<String> {
+    // ASCII lowercasing keeps byte offsets the same as the original
+    let lower = content.to_ascii_lowercase();
+    let mut codes = Vec::new();
+    for prefix in INVITE_PREFIXES.iter() {
+        for (i, _) in lower.match_indices(prefix) {
+            let code: String = content[i + prefix.len()..]
+                .chars()
+                .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+                .collect();
+            if !code.is_empty() {
+                codes.push(code);
+            }
+        }
+    }
+
+    codes
+}
+
+/// How many of the recent messages have the same content. Messages with no text, like
+/// images or embeds on their own, are never counted as duplicates.
+fn count_duplicates(recent: &VecDeque<(DateTime<Utc>, String)>, content: &str) -> usize {
+    if content.trim().is_empty() {
+        return 0;
+    }
+    recent.iter().filter(|(_, c)| c == content).count()
+}
+
+/// Drops members who haven't posted recently and old invite lookups. Runs at most once per
+/// sweep interval.
+async fn sweep_trackers(
+    ctx: &Context,
+    tracker: &Arc<Mutex<HashMap<(GuildId, UserId), VecDeque<(DateTime<Utc>, String)>>>>,
+) {
+    let now = Utc::now();
+    let last = LAST_SWEEP.load(Ordering::SeqCst);
+    if now.timestamp() - last < TRACKER_SWEEP_SECS
+        || LAST_SWEEP
+            .compare_exchange(last, now.timestamp(), Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+    {
+        return;
+    }
+    let cutoff = now - chrono::Duration::seconds(TRACKER_RETENTION_SECS);
+
+    tracker
+        .lock()
+        .await
+        .retain(|_, recent| recent.back().map_or(false, |(t, _)| *t >= cutoff));
+
+    let invites = {
+        let data = ctx.data.read().await;
+        data.get::<InviteCache>().unwrap().clone()
+    };
+    invites.lock().await.retain(|_, (t, _)| *t >= cutoff);
+}
+
+/// Finds the server an invite points to, using earlier lookups where possible.
+async fn invite_guild(ctx: &Context, code: &str) -> Option<GuildId> {
+    let invites = {
+        let data = ctx.data.read().await;
+        data.get::<InviteCache>().unwrap().clone()
+    };
+    if let Some((_, guild)) = invites.lock().await.get(code) {
+        return *guild;
+    }
+
+    let guild = match ctx.http.get_invite(code, false).await {
+        Ok(invite) => invite.guild.map(|g| g.id),
+        Err(_) => None,
+    };
+    invites
+        .lock()
+        .await
+        .insert(code.to_string(), (Utc::now(), guild));
+
+    guild
+}
+
+/// Checks the message against the guild's spam thresholds, recording it for later checks.
+async fn detect_spam(
+    ctx: &Context,
+    tracker: &Arc<Mutex<HashMap<(GuildId, UserId), VecDeque<(DateTime<Utc>, String)>>>>,
+    settings: &GuildSettings,
+    guild: GuildId,
+    msg: &Message,
+) -> Option<SpamKind> {
+    let setting = |key: &str| settings.get::<u64>(key).unwrap_or(0) as usize;
+
+    let mention_limit = setting("spam_mention_limit");
+    let mentions = msg.mentions.len() + msg.mention_roles.len() + if msg.mention_everyone { 1 } else { 0 };
+    if mention_limit != 0 && mentions >= mention_limit {
+        return Some(SpamKind::Mentions(mentions));
+    }
+
+    let duplicate_limit = setting("spam_duplicate_messages");
+    let rapid_limit = setting("spam_rapid_messages");
+    if duplicate_limit != 0 || rapid_limit != 0 {
+        let window = chrono::Duration::seconds(setting("spam_window") as i64);
+        let now = Utc::now();
+        let mut tracker = tracker.lock().await;
+        let recent = tracker
+            .entry((guild, msg.author.id))
+            .or_insert_with(VecDeque::new);
+        recent.push_back((now, msg.content.clone()));
+        while let Some((t, _)) = recent.front() {
+            if *t < now - window {
+                recent.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        let duplicates = count_duplicates(recent, &msg.content);
+        let kind = if duplicate_limit != 0 && duplicates >= duplicate_limit {
+            Some(SpamKind::Duplicate(duplicates))
+        } else if rapid_limit != 0 && recent.len() >= rapid_limit {
+            Some(SpamKind::Rapid(recent.len()))
+        } else {
+            None
+        };
+        if kind.is_some() {
+            // Start counting again so one burst is only acted on once
+            tracker.remove(&(guild, msg.author.id));
+            return kind;
+        }
+    }
+
+    if setting("spam_block_invites") != 0 {
+        for code in find_invite_codes(&msg.content)
+            .into_iter()
+            .take(MAX_INVITE_LOOKUPS)
+        {
+            if let Some(invite_guild) = invite_guild(ctx, &code).await {
+                if invite_guild.to_guild_cached(&ctx).await.is_none() {
+                    return Some(SpamKind::Invite(code));
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Runs a guild message through spam detection and applies the guild's `spam_action` setting.
+pub async fn check_spam(
+    ctx: &Context,
+    tracker: &Arc<Mutex<HashMap<(GuildId, UserId), VecDeque<(DateTime<Utc>, String)>>>>,
+    msg: &Message,
+) -> Result<(), String> {
+    let guild = match msg.guild_id {
+        Some(g) => g,
+        None => return Ok(()),
+    };
+    if msg.author.bot {
+        return Ok(());
+    }
+    let roles = match &msg.member {
+        Some(m) => m.roles.clone(),
+        None => match guild.member(ctx, msg.author.id).await {
+            Ok(m) => m.roles,
+            Err(_) => Vec::new(),
+        },
+    };
+    if is_exempt(ctx, guild, Some(msg.channel_id), &roles).await? {
+        return Ok(());
+    }
+    sweep_trackers(ctx, tracker).await;
+
+    let settings = get_guild_settings(ctx, guild).await?;
+    let kind = match detect_spam(ctx, tracker, &settings, guild, msg).await {
+        Some(k) => k,
+        None => return Ok(()),
+    };
+    let reason = kind.describe();
+
+    msg.delete(ctx).await.map_err(|e| e.to_string())?;
+
+    let bot_user = get_bot_user(ctx).await?;
+    let action = ModAction {
+        target: Some(msg.author.id),
+        moderator: bot_user.clone(),
+        action_type: ModActionType::Spam,
+        reason: Some(reason.clone()),
+        details: None,
+        guild,
+        case_id: None,
+        old_value: Some(msg.content.clone()),
+        new_value: None,
+    };
+    log_mod_action(action, ctx).await;
+
+    let mode = settings
+        .get::<String>("spam_action")
+        .unwrap_or_else(|| String::from("warn"));
+    let (action_type, consequence) = match mode.as_str() {
+        "timeout" => {
+            let timeout_role = match settings.get::<u64>("timeout_role") {
+                Some(r) if r != 0 => RoleId(r),
+                _ => return Err(String::from("Timeout role is not configured")),
+            };
+            let mut member = guild
+                .member(ctx, msg.author.id)
+                .await
+                .map_err(|e| e.to_string())?;
+            member
+                .add_role(&ctx.http, timeout_role)
+                .await
+                .map_err(|e| e.to_string())?;
+            (ModActionType::Timeout, "placed in timeout")
+        }
+        "kick" => {
+            guild
+                .kick(&ctx.http, msg.author.id)
+                .await
+                .map_err(|e| e.to_string())?;
+            (ModActionType::Kick, "kicked")
+        }
+        "warn" => {
+            msg.channel_id
+                .send_message(&ctx.http, |m| {
+                    m.embed(|e| {
+                        e.title("Warning - Spam");
+                        e.description("Do not spam in this server.");
+                        e.fields(vec![("User:", format!("<@{}>", msg.author.id.as_u64()), false)]);
+                        e.field("Reason", &reason, false);
+                        e.color(Colour::RED);
+
+                        e
+                    });
+
+                    m
+                })
+                .await
+                .map_err(|e| e.to_string())?;
+            return Ok(());
+        }
+        _ => return Ok(()),
+    };
+
+    let action = ModAction {
+        target: Some(msg.author.id),
+        moderator: bot_user,
+        action_type,
+        reason: Some(reason),
+        details: Some(String::from("Automatic spam detection")),
+        guild,
+        case_id: None,
+        old_value: None,
+        new_value: None,
+    };
+    log_mod_action(action, ctx).await;
+    debug!("{} was {} for spam", msg.author.id.as_u64(), consequence);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(messages: &[&str]) -> VecDeque<(DateTime<Utc>, String)> {
+        messages.iter().map(|m| (Utc::now(), m.to_string())).collect()
+    }
+
+    #[test]
+    fn duplicates_count_matching_text() {
+        let recent = history(&["hi", "spam", "spam", "spam"]);
+        assert_eq!(count_duplicates(&recent, "spam"), 3);
+        assert_eq!(count_duplicates(&recent, "hi"), 1);
+    }
+
+    #[test]
+    fn messages_without_text_are_never_duplicates() {
+        let recent = history(&["", "", " ", ""]);
+        assert_eq!(count_duplicates(&recent, ""), 0);
+        assert_eq!(count_duplicates(&recent, " "), 0);
+    }
+
+    #[test]
+    fn finds_invite_codes() {
+        assert_eq!(
+            find_invite_codes("join discord.gg/AbC-12 or https://Discord.com/invite/xyz!"),
+            vec![String::from("AbC-12"), String::from("xyz")]
+        );
+        assert!(find_invite_codes("no invites here").is_empty());
+    }
+}
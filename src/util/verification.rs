@@ -3,19 +3,367 @@
  *   All rights reserved.
  */
 
+use crate::models::{Verification, VerificationRequest, VerificationType};
 use crate::prelude::*;
+use crate::util::moderation::{get_bot_user, log_mod_action, ModAction, ModActionType};
+use crate::util::settings::{get_guild_settings, GuildSettings};
+use chrono::{DateTime, Utc};
+use serenity::builder::CreateEmbed;
 use serenity::model::channel::{Reaction, ReactionType};
+use serenity::model::id::{ChannelId, GuildId, MessageId, RoleId};
+use serenity::model::user::User;
+use sqlx::PgPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-enum VerifyType {
-    Eagle,
-    SummitSilver,
-    CampStaff,
-    Ypt,
-    Ordeal,
-    Brotherhood,
-    Vigil,
-    Quartermaster,
-    Close,
+/// Where verification requests are queued for review
+pub const VERIFY_CHANNEL: ChannelId = ChannelId(684577265425973285);
+
+/// Closes a request without verifying anything or giving a reason
+pub const CLOSE_EMOJI: &str = "❌";
+
+/// How often verifications are checked for expiry
+const EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// How many days before a verification expires its holder is reminded to renew it
+const EXPIRY_REMINDER_DAYS: i32 = 30;
+
+/// Set once the expiry check is running, since `ready` fires again on every reconnect
+static EXPIRY_SCHEDULED: AtomicBool = AtomicBool::new(false);
+
+/// Every verification type, in the order they were added. Retired types can't be verified
+/// any more but are kept so existing verifications still show.
+pub async fn get_verification_types(
+    pg_pool: &PgPool,
+    include_retired: bool,
+) -> Result<Vec<VerificationType>, String> {
+    sqlx::query_as!(
+        VerificationType,
+        "SELECT * FROM verification_types WHERE ($1 OR NOT retired) ORDER BY id",
+        include_retired
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Finds a type by its key or name, e.g. `eagle` or `Eagle Scout`.
+pub fn find_verification_type<'a>(
+    types: &'a [VerificationType],
+    input: &str,
+) -> Option<&'a VerificationType> {
+    let input = input.trim().to_lowercase();
+    types
+        .iter()
+        .find(|t| t.key == input || t.name.to_lowercase() == input)
+}
+
+/// The award name for a stored key, or the key itself if the type no longer exists.
+pub fn award_name(types: &[VerificationType], key: &str) -> String {
+    match types.iter().find(|t| t.key == key) {
+        Some(t) => t.name.clone(),
+        None => key.to_string(),
+    }
+}
+
+/// The guild setting holding the role a server gives for an award.
+pub fn award_role_setting(award: &VerificationType) -> String {
+    format!("verified_role_{}", award.key)
+}
+
+/// Preset reasons for denying a request. Reviewers can also deny with their own reason.
+#[derive(Clone, Copy)]
+pub enum DenyReason {
+    InsufficientProof,
+    WrongAward,
+    Illegible,
+}
+
+pub const DENY_REASONS: [DenyReason; 3] = [
+    DenyReason::InsufficientProof,
+    DenyReason::WrongAward,
+    DenyReason::Illegible,
+];
+
+impl DenyReason {
+    pub fn key(&self) -> &'static str {
+        match self {
+            DenyReason::InsufficientProof => "insufficient",
+            DenyReason::WrongAward => "wrong-award",
+            DenyReason::Illegible => "illegible",
+        }
+    }
+
+    /// What the requester is told, including what to send instead.
+    pub fn description(&self) -> &'static str {
+        match self {
+            DenyReason::InsufficientProof => "Insufficient proof. The proof doesn't show that you completed the award.",
+            DenyReason::WrongAward => "Wrong award. The proof is for a different award than the one you requested.",
+            DenyReason::Illegible => "Illegible proof. The image is too blurry, dark or cropped to read.",
+        }
+    }
+
+    pub fn from_key(key: &str) -> Option<DenyReason> {
+        DENY_REASONS
+            .iter()
+            .find(|r| r.key() == key.trim().to_lowercase())
+            .copied()
+    }
+}
+
+/// Records that the user has verified the award, setting its expiry from the type's
+/// `valid_days`. Verifying an award the user already has renews it.
+pub async fn set_verified(
+    pg_pool: &PgPool,
+    user: UserId,
+    award: &VerificationType,
+    approver: UserId,
+) -> Result<(), String> {
+    sqlx::query!(
+        "INSERT INTO verified_roles (userid, award, approver, expires_at) VALUES ($1, $2, $3, NOW() + make_interval(days => $4)) ON CONFLICT (userid, award) WHERE revoked_at IS NULL DO UPDATE SET approver = EXCLUDED.approver, verified_at = NOW(), expires_at = EXCLUDED.expires_at, expiry_notified = FALSE",
+        user.as_u64().to_string(),
+        award.key,
+        approver.as_u64().to_string(),
+        award.valid_days
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Revokes a verified award, keeping the record of it. Returns false if the user didn't have it.
+pub async fn revoke_verified(
+    pg_pool: &PgPool,
+    user: UserId,
+    award: &VerificationType,
+    revoked_by: UserId,
+    reason: Option<&str>,
+) -> Result<bool, String> {
+    let revoked = sqlx::query!(
+        "UPDATE verified_roles SET revoked_at = NOW(), revoked_by = $3, revoke_reason = $4 WHERE userid = $1 AND award = $2 AND revoked_at IS NULL",
+        user.as_u64().to_string(),
+        award.key,
+        revoked_by.as_u64().to_string(),
+        reason.map(|r| r.to_string())
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(revoked > 0)
+}
+
+/// Every award the user currently has verified, including retired ones, with its verification.
+pub async fn get_verified(
+    pg_pool: &PgPool,
+    user: UserId,
+) -> Result<Vec<(Verification, VerificationType)>, String> {
+    let verifications = sqlx::query_as!(
+        Verification,
+        "SELECT * FROM verified_roles WHERE userid = $1 AND revoked_at IS NULL",
+        user.as_u64().to_string()
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    let types = get_verification_types(pg_pool, true).await?;
+    let mut verified: Vec<(Verification, VerificationType)> = verifications
+        .into_iter()
+        .filter_map(|v| {
+            let award = types.iter().find(|t| t.key == v.award)?.clone();
+            Some((v, award))
+        })
+        .collect();
+    verified.sort_by_key(|(_, t)| t.id);
+
+    Ok(verified)
+}
+
+/// The role a guild gives for an award, if it has set one.
+fn award_role(settings: &GuildSettings, award: &VerificationType) -> Option<RoleId> {
+    match settings.get::<u64>(&award_role_setting(award)) {
+        Some(r) if r != 0 => Some(RoleId(r)),
+        _ => None,
+    }
+}
+
+/// Gives a member the roles their guild has set for each of their verified awards.
+pub async fn grant_verified_roles(
+    ctx: &Context,
+    pg_pool: &PgPool,
+    guild: GuildId,
+    member: &mut Member,
+) -> Result<(), String> {
+    let settings = GuildSettings::load(pg_pool, guild).await?;
+    for (_, award) in get_verified(pg_pool, member.user.id).await? {
+        if let Some(role) = award_role(&settings, &award) {
+            if !member.roles.contains(&role) {
+                member
+                    .add_role(&ctx.http, role)
+                    .await
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn sync_award_role(
+    ctx: &Context,
+    guild: GuildId,
+    user: UserId,
+    award: &VerificationType,
+    grant: bool,
+) -> Result<(), String> {
+    let settings = get_guild_settings(ctx, guild).await?;
+    let role = match award_role(&settings, award) {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+    // Not a member of this guild
+    let mut member = match guild.member(ctx, user).await {
+        Ok(m) => m,
+        Err(_) => return Ok(()),
+    };
+
+    if grant && !member.roles.contains(&role) {
+        member
+            .add_role(&ctx.http, role)
+            .await
+            .map_err(|e| e.to_string())?;
+    } else if !grant && member.roles.contains(&role) {
+        member
+            .remove_role(&ctx.http, role)
+            .await
+            .map_err(|e| e.to_string())?;
+    }
+
+    Ok(())
+}
+
+/// Adds or removes an award's role in every server the user shares with the bot. A failure
+/// in one server is logged and doesn't stop the rest.
+pub async fn sync_award_roles(ctx: &Context, user: UserId, award: &VerificationType, grant: bool) {
+    for guild in ctx.cache.guilds().await {
+        if let Err(err) = sync_award_role(ctx, guild, user, award, grant).await {
+            error!(
+                "Error updating {} role for {} in {}: {}",
+                award.name,
+                user.as_u64(),
+                guild.as_u64(),
+                err
+            );
+        }
+    }
+}
+
+/// Fetches a request by its ID.
+pub async fn get_request(pg_pool: &PgPool, id: i32) -> Result<Option<VerificationRequest>, String> {
+    sqlx::query_as!(
+        VerificationRequest,
+        "SELECT * FROM verification_requests WHERE id = $1",
+        id
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Fetches the request a message in the verification channel belongs to.
+async fn get_request_by_message(pg_pool: &PgPool, message: MessageId) -> Result<Option<VerificationRequest>, String> {
+    sqlx::query_as!(
+        VerificationRequest,
+        "SELECT * FROM verification_requests WHERE queue_message = $1",
+        message.as_u64().to_string()
+    )
+    .fetch_optional(pg_pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Every request still waiting for a reviewer, oldest first.
+pub async fn get_pending_requests(pg_pool: &PgPool) -> Result<Vec<VerificationRequest>, String> {
+    sqlx::query_as!(
+        VerificationRequest,
+        "SELECT * FROM verification_requests WHERE status = 'pending' ORDER BY created_at"
+    )
+    .fetch_all(pg_pool)
+    .await
+    .map_err(|e| e.to_string())
+}
+
+/// Fills in the queue message embed for a request.
+fn request_embed<'a>(
+    e: &'a mut CreateEmbed,
+    request: &VerificationRequest,
+    types: &[VerificationType],
+) -> &'a mut CreateEmbed {
+    let attachments: Vec<&str> = request.attachments.lines().collect();
+    e.title(format!("Verification Request #{}", request.id));
+    e.description(
+        attachments
+            .iter()
+            .enumerate()
+            .map(|(i, url)| format!("[Attachment {}]({})", i + 1, url))
+            .collect::<Vec<String>>()
+            .join("\n"),
+    );
+    e.field("Requester", format!("<@{}>", request.requester), true);
+    e.field(
+        "Requested Award",
+        match &request.requested_award {
+            Some(a) => award_name(types, a),
+            None => String::from("Not specified"),
+        },
+        true,
+    );
+    e.field("Status", &request.status, true);
+    if let Some(reviewer) = &request.reviewer {
+        e.field("Reviewer", format!("<@{}>", reviewer), true);
+    }
+    if request.resubmissions > 0 {
+        e.field("Resubmissions", request.resubmissions, true);
+    }
+    if let Some(reason) = &request.deny_reason {
+        if request.status == "pending" {
+            e.field("Previously Denied For", reason, false);
+        } else {
+            e.field("Reason", reason, false);
+        }
+    }
+    if let Some(url) = attachments.get(0) {
+        e.image(url);
+    }
+    e.colour(match request.status.as_str() {
+        "approved" => Colour::DARK_GREEN,
+        "denied" => Colour::RED,
+        "closed" => Colour::LIGHT_GREY,
+        _ => Colour::BLUE,
+    });
+    e.footer(|f| {
+        f.text(format!("Submitted {}", request.created_at.date().naive_utc()));
+        f
+    });
+
+    e
+}
+
+/// Splits a `#<request id>` reference off a DM, used to resubmit a denied request.
+fn parse_resubmission(content: &str) -> (Option<i32>, String) {
+    let mut id = None;
+    let mut rest = Vec::new();
+    for word in content.split_whitespace() {
+        match word.strip_prefix('#').and_then(|n| n.parse::<i32>().ok()) {
+            Some(n) if id.is_none() => id = Some(n),
+            _ => rest.push(word),
+        }
+    }
+
+    (id, rest.join(" "))
 }
 
 pub async fn handle_verification_file(ctx: &Context, msg: &Message) -> Result<(), String> {
@@ -23,24 +371,79 @@ pub async fn handle_verification_file(ctx: &Context, msg: &Message) -> Result<()
         return Err(String::from("No attachments found"));
     }
 
-    let http_cache = &ctx.http;
+    let bot_data = ctx.data.read().await;
+    let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+    let attachments = msg
+        .attachments
+        .iter()
+        .map(|a| a.url.clone())
+        .collect::<Vec<String>>()
+        .join("\n");
+    let types = get_verification_types(pg_pool, false).await?;
+    let (resubmit_id, content) = parse_resubmission(&msg.content);
+    let requested_award = find_verification_type(&types, &content).map(|t| t.key.clone());
 
-    let verify_channel = match http_cache.get_channel(684577265425973285).await {
-        Ok(c) => c,
-        Err(err) => {
-            return Err(err.to_string());
+    let request = match resubmit_id {
+        Some(id) => {
+            // Earlier attachments are kept alongside the new ones
+            let request = sqlx::query_as!(
+                VerificationRequest,
+                "UPDATE verification_requests SET status = 'pending', attachments = attachments || chr(10) || $1, requested_award = COALESCE($2, requested_award), resubmissions = resubmissions + 1, reviewer = NULL, decided_at = NULL WHERE id = $3 AND requester = $4 AND status = 'denied' RETURNING *",
+                attachments,
+                requested_award,
+                id,
+                msg.author.id.as_u64().to_string()
+            )
+            .fetch_optional(pg_pool)
+            .await
+            .map_err(|e| e.to_string())?;
+            match request {
+                Some(r) => r,
+                None => {
+                    if let Err(err) = msg
+                        .channel_id
+                        .send_message(&ctx, |m| {
+                            m.embed(|e| {
+                                e.title("Verification Request Status Update");
+                                e.description(format!(
+                                    "Request #{} can't be resubmitted. Only your own denied requests can be resubmitted; send your proof without a request number to open a new one.",
+                                    id
+                                ));
+                                e.colour(Colour::RED);
+                                e.footer(|f| {
+                                    f.text("DSC Bot | Powered by Rusty Development");
+                                    f
+                                });
+                                e
+                            });
+                            m
+                        })
+                        .await
+                    {
+                        return Err(err.to_string());
+                    }
+                    return Ok(());
+                }
+            }
         }
+        None => match sqlx::query_as!(
+            VerificationRequest,
+            "INSERT INTO verification_requests (requester, attachments, requested_award) VALUES ($1, $2, $3) RETURNING *",
+            msg.author.id.as_u64().to_string(),
+            attachments,
+            requested_award
+        )
+        .fetch_one(pg_pool)
+        .await
+        {
+            Err(err) => return Err(err.to_string()),
+            Ok(r) => r,
+        },
     };
 
-    let verify_channel_id = verify_channel.id();
-
-    let verify_message = match verify_channel_id
+    let verify_message = match VERIFY_CHANNEL
         .send_message(&ctx, |m| {
-            m.content(format!(
-                "{}\n{}",
-                &msg.author.id.as_u64().to_string(),
-                msg.attachments.get(0).unwrap().url
-            ));
+            m.embed(|e| request_embed(e, &request, &types));
             m
         })
         .await
@@ -49,11 +452,20 @@ pub async fn handle_verification_file(ctx: &Context, msg: &Message) -> Result<()
         Ok(m) => m,
     };
 
-    let emoji_vec = define_emoji_vec();
+    if let Err(err) = sqlx::query!(
+        "UPDATE verification_requests SET queue_message = $1 WHERE id = $2",
+        verify_message.id.as_u64().to_string(),
+        request.id
+    )
+    .execute(pg_pool)
+    .await
+    {
+        return Err(err.to_string());
+    }
 
-    for s in emoji_vec {
+    for emoji in review_emojis(&types) {
         if let Err(err) = verify_message
-            .react(http_cache, s.chars().next().unwrap())
+            .react(&ctx.http, ReactionType::Unicode(emoji))
             .await
         {
             return Err(err.to_string());
@@ -65,7 +477,12 @@ pub async fn handle_verification_file(ctx: &Context, msg: &Message) -> Result<()
         .send_message(&ctx, |m| {
             m.embed(|e| {
                 e.title("Verification Request Status Update");
-                e.description("Request Submitted");
+                if resubmit_id.is_some() {
+                    e.description("Request Resubmitted");
+                } else {
+                    e.description("Request Submitted");
+                }
+                e.field("Request ID", format!("#{}", request.id), true);
                 e.colour(Colour::BLUE);
                 e.footer(|f| {
                     f.text("DSC Bot | Powered by Rusty Development");
@@ -94,99 +511,162 @@ pub async fn handle_verification_reaction(
     if react.user_id.as_u64() == current_info.id.as_u64() {
         return Ok(String::from(""));
     }
-    let message_id = react.message_id;
-    let http_cache = &ctx.http;
-    let message = match http_cache
-        .get_message(684577265425973285, *message_id.as_u64())
-        .await
-    {
-        Ok(m) => m,
-        Err(err) => return Err(err.to_string()),
-    };
 
-    let emoji_used = match react.emoji {
-        ReactionType::Unicode(e) => e,
+    let emoji_used = match &react.emoji {
+        ReactionType::Unicode(e) => e.clone(),
         _ => {
             return Err(String::from("Invalid reaction"));
         }
     };
 
-    let emoji_vec = define_emoji_vec();
-
-    let verify_type = match match_verify_type(&emoji_used) {
-        Some(v) => v,
-        None => return Err(String::from("Invalid verify type")),
+    let (request, types) = {
+        let bot_data = ctx.data.read().await;
+        let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+        let request = match get_request_by_message(pg_pool, react.message_id).await? {
+            Some(r) => r,
+            None => return Err(String::from("Not a verification request")),
+        };
+        (request, get_verification_types(pg_pool, false).await?)
     };
+    let award = types.iter().find(|t| t.emoji == emoji_used);
+    let deny_reason = match_deny_reason(&emoji_used);
+    if award.is_none() && deny_reason.is_none() && emoji_used != CLOSE_EMOJI {
+        return Err(String::from("Invalid verify type"));
+    }
 
-    let message_content = &message.content;
-    let user_id = message_content.split("\n").clone();
-    let split_contents = user_id.collect::<Vec<&str>>();
-    let user_id_str: &str = split_contents.get(0).unwrap();
-
-    let user = match http_cache
-        .get_user(split_contents.get(0).unwrap().parse::<u64>().unwrap())
-        .await
-    {
-        Err(err) => return Err(err.to_string()),
+    let reviewer = match react.user_id.to_user(ctx).await {
         Ok(u) => u,
-    };
-
-    let priv_chan = match user.create_dm_channel(http_cache).await {
-        Ok(c) => c,
         Err(err) => return Err(err.to_string()),
     };
 
-    let mut verify_db = match verify_type {
-        VerifyType::Eagle => get_global_pickle_database("eagle.db"),
-        VerifyType::SummitSilver => get_global_pickle_database("summit.db"),
-        VerifyType::CampStaff => get_global_pickle_database("campstaff.db"),
-        VerifyType::Ypt => get_global_pickle_database("ypt.db"),
-        VerifyType::Ordeal => get_global_pickle_database("ordeal.db"),
-        VerifyType::Brotherhood => get_global_pickle_database("brotherhood.db"),
-        VerifyType::Vigil => get_global_pickle_database("vigil.db"),
-        VerifyType::Quartermaster => get_global_pickle_database("quartermaster.db"),
-        VerifyType::Close => {
-            if let Err(err) = priv_chan
-                .send_message(&ctx, |m| {
-                    m.embed(|e| {
-                        e.title("Verification");
-                        e.description("Verification Request Closed.");
-                        e.colour(Colour::BLUE);
-                        e.footer(|f| {
-                            f.text("DSC Bot | Powered by Rusty Development");
-                            f
-                        });
-                        e
-                    });
-                    m
-                })
-                .await
-            {
-                return Err(format!(
-                    "Could not send closed request message to {}: {:?}",
-                    user_id_str.to_string(),
-                    err
-                ));
-            }
-            if let Err(err) = message.delete(http_cache).await {
-                return Err(format!(
-                    "Could not delete verification message: {:?}",
-                    err.to_string()
-                ));
-            }
-            return Ok(String::from("Request closed"));
+    match (award, deny_reason) {
+        (Some(award), _) => {
+            approve_request(ctx, &request, award, &reviewer, react.guild_id).await?;
+            Ok(String::from(""))
+        }
+        (None, Some(reason)) => {
+            close_request(ctx, &request, &reviewer, react.guild_id, Some(reason.description())).await?;
+            Ok(String::from("Request denied"))
         }
+        (None, None) => {
+            close_request(ctx, &request, &reviewer, react.guild_id, None).await?;
+            Ok(String::from("Request closed"))
+        }
+    }
+}
+
+/// Marks a pending request as decided. Fails if another reviewer got to it first.
+async fn decide_request(
+    pg_pool: &PgPool,
+    request: &VerificationRequest,
+    status: &str,
+    reviewer: &User,
+    reason: Option<&str>,
+) -> Result<(), String> {
+    let updated = sqlx::query!(
+        "UPDATE verification_requests SET status = $1, reviewer = $2, decided_at = NOW(), deny_reason = $3 WHERE id = $4 AND status = 'pending'",
+        status,
+        reviewer.id.as_u64().to_string(),
+        reason,
+        request.id
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(format!("Request #{} has already been reviewed", request.id));
+    }
+
+    sqlx::query!(
+        "INSERT INTO verification_decisions (request_id, reviewer, status, reason) VALUES ($1, $2, $3, $4)",
+        request.id,
+        reviewer.id.as_u64().to_string(),
+        status,
+        reason
+    )
+    .execute(pg_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+/// Redraws a request's queue message after its status changes.
+async fn refresh_queue_message(ctx: &Context, pg_pool: &PgPool, id: i32) -> Result<(), String> {
+    let request = match get_request(pg_pool, id).await? {
+        Some(r) => r,
+        None => return Ok(()),
+    };
+    let message = match request
+        .queue_message
+        .as_ref()
+        .and_then(|m| m.parse::<u64>().ok())
+    {
+        Some(m) => MessageId(m),
+        None => return Ok(()),
     };
 
-    if let Err(err) = verify_db.set(&user_id_str, &1) {
-        return Err(err.to_string());
+    let types = get_verification_types(pg_pool, true).await?;
+    VERIFY_CHANNEL
+        .edit_message(&ctx.http, message, |m| m.embed(|e| request_embed(e, &request, &types)))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+fn requester_id(request: &VerificationRequest) -> Result<UserId, String> {
+    request
+        .requester
+        .parse::<u64>()
+        .map(UserId)
+        .map_err(|e| e.to_string())
+}
+
+/// Approves a request, records the award and lets the requester know.
+pub async fn approve_request(
+    ctx: &Context,
+    request: &VerificationRequest,
+    award: &VerificationType,
+    reviewer: &User,
+    guild: Option<GuildId>,
+) -> Result<(), String> {
+    let requester = requester_id(request)?;
+    {
+        let bot_data = ctx.data.read().await;
+        let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+        decide_request(pg_pool, request, "approved", reviewer, None).await?;
+        set_verified(pg_pool, requester, award, reviewer.id).await?;
+        refresh_queue_message(ctx, pg_pool, request.id).await?;
     }
+    sync_award_roles(ctx, requester, award, true).await;
+
+    log_verification(
+        ctx,
+        guild,
+        reviewer,
+        requester,
+        ModActionType::Verification,
+        Some(award.name.clone()),
+        None,
+    )
+    .await;
 
+    let priv_chan = requester
+        .create_dm_channel(&ctx.http)
+        .await
+        .map_err(|e| e.to_string())?;
     if let Err(err) = priv_chan
         .send_message(&ctx, |m| {
             m.embed(|e| {
                 e.title("Verification Request Status Update");
                 e.description("Successfully verified.");
+                e.field("Award", &award.name, true);
+                e.field("Request ID", format!("#{}", request.id), true);
+                if let Some(days) = award.valid_days {
+                    let expires_at = Utc::now() + chrono::Duration::days(days as i64);
+                    e.field("Expires", expires_at.date().naive_utc(), true);
+                }
                 e.colour(Colour::DARK_GREEN);
                 e.footer(|f| {
                     f.text("DSC Bot | Powered by Rusty Development");
@@ -204,27 +684,266 @@ pub async fn handle_verification_reaction(
         ));
     }
 
-    Ok(String::from(""))
+    Ok(())
+}
+
+/// Closes a request without verifying anything. Requests closed with a reason are denied.
+pub async fn close_request(
+    ctx: &Context,
+    request: &VerificationRequest,
+    reviewer: &User,
+    guild: Option<GuildId>,
+    reason: Option<&str>,
+) -> Result<(), String> {
+    let requester = requester_id(request)?;
+    let status = if reason.is_some() { "denied" } else { "closed" };
+    let types = {
+        let bot_data = ctx.data.read().await;
+        let pg_pool = bot_data.get::<ConnectionPool>().unwrap();
+        decide_request(pg_pool, request, status, reviewer, reason).await?;
+        refresh_queue_message(ctx, pg_pool, request.id).await?;
+        get_verification_types(pg_pool, true).await?
+    };
+
+    log_verification(
+        ctx,
+        guild,
+        reviewer,
+        requester,
+        if reason.is_some() {
+            ModActionType::VerificationDenied
+        } else {
+            ModActionType::VerificationClosed
+        },
+        request.requested_award.as_ref().map(|a| award_name(&types, a)),
+        reason.map(|r| r.to_string()),
+    )
+    .await;
+
+    let priv_chan = requester
+        .create_dm_channel(&ctx.http)
+        .await
+        .map_err(|e| e.to_string())?;
+    if let Err(err) = priv_chan
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Verification");
+                match reason {
+                    Some(r) => {
+                        e.description("Verification Request Denied.");
+                        e.field("Reason", r, false);
+                        e.field(
+                            "Resubmitting",
+                            format!(
+                                "Send new proof with `#{}` in your message to resubmit this request.",
+                                request.id
+                            ),
+                            false,
+                        );
+                        e.colour(Colour::RED);
+                    }
+                    None => {
+                        e.description("Verification Request Closed.");
+                        e.colour(Colour::BLUE);
+                    }
+                }
+                e.field("Request ID", format!("#{}", request.id), true);
+                e.footer(|f| {
+                    f.text("DSC Bot | Powered by Rusty Development");
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await
+    {
+        return Err(format!(
+            "Could not send closed request message to {}: {:?}",
+            request.requester, err
+        ));
+    }
+
+    Ok(())
+}
+
+/// Lets a user know their verification is about to expire, or has expired.
+async fn notify_expiry(
+    ctx: &Context,
+    user: UserId,
+    award: &VerificationType,
+    expires_at: DateTime<Utc>,
+    expired: bool,
+) -> Result<(), String> {
+    let priv_chan = user
+        .create_dm_channel(&ctx.http)
+        .await
+        .map_err(|e| e.to_string())?;
+    priv_chan
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Verification Expiry");
+                if expired {
+                    e.description(format!("Your {} verification has expired.", award.name));
+                    e.colour(Colour::RED);
+                } else {
+                    e.description(format!(
+                        "Your {} verification expires on {}.",
+                        award.name,
+                        expires_at.date().naive_utc()
+                    ));
+                    e.colour(Colour::GOLD);
+                }
+                e.field("Renewing", format!("Send me your new proof to renew it. {}", award.proof), false);
+                e.footer(|f| {
+                    f.text("DSC Bot | Powered by Rusty Development");
+                    f
+                });
+                e
+            });
+            m
+        })
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(())
 }
 
-fn define_emoji_vec<'a>() -> Vec<&'a str> {
-    let emoji_vec = vec!["🦅", "⛰", "🏕", "🛂", "↗", "🟥", "🔺", "🚢", "❌", "⚠", "⛔"];
+/// Reminds users of verifications expiring soon, then revokes the ones that have lapsed.
+async fn check_verification_expiry(ctx: &Context) -> Result<(), String> {
+    let pg_pool = {
+        let bot_data = ctx.data.read().await;
+        bot_data.get::<ConnectionPool>().unwrap().clone()
+    };
+    let types = get_verification_types(&pg_pool, true).await?;
+
+    let expiring = sqlx::query_as!(
+        Verification,
+        "SELECT * FROM verified_roles WHERE revoked_at IS NULL AND NOT expiry_notified AND expires_at > NOW() AND expires_at <= NOW() + make_interval(days => $1)",
+        EXPIRY_REMINDER_DAYS
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    for verification in expiring {
+        let (user, award, expires_at) = match (
+            verification.userid.parse::<u64>(),
+            types.iter().find(|t| t.key == verification.award),
+            verification.expires_at,
+        ) {
+            (Ok(u), Some(a), Some(t)) => (UserId(u), a, t),
+            _ => continue,
+        };
+        if let Err(err) = notify_expiry(ctx, user, award, expires_at, false).await {
+            error!("Could not send expiry reminder to {}: {}", user.as_u64(), err);
+        }
+        // Marked even if the DM failed, so users with closed DMs aren't retried every hour
+        sqlx::query!(
+            "UPDATE verified_roles SET expiry_notified = TRUE WHERE id = $1",
+            verification.id
+        )
+        .execute(&pg_pool)
+        .await
+        .map_err(|e| e.to_string())?;
+    }
+
+    let lapsed = sqlx::query_as!(
+        Verification,
+        "SELECT * FROM verified_roles WHERE revoked_at IS NULL AND expires_at <= NOW()"
+    )
+    .fetch_all(&pg_pool)
+    .await
+    .map_err(|e| e.to_string())?;
+    if lapsed.is_empty() {
+        return Ok(());
+    }
+    let bot_id = get_bot_user(ctx).await?.id;
+    for verification in lapsed {
+        let (user, award, expires_at) = match (
+            verification.userid.parse::<u64>(),
+            types.iter().find(|t| t.key == verification.award),
+            verification.expires_at,
+        ) {
+            (Ok(u), Some(a), Some(t)) => (UserId(u), a, t),
+            _ => continue,
+        };
+        revoke_verified(&pg_pool, user, award, bot_id, Some("Expired")).await?;
+        sync_award_roles(ctx, user, award, false).await;
+        if let Err(err) = notify_expiry(ctx, user, award, expires_at, true).await {
+            error!("Could not send expiry notice to {}: {}", user.as_u64(), err);
+        }
+        info!("{}'s {} verification expired", user.as_u64(), award.name);
+    }
+
+    Ok(())
+}
+
+/// Starts checking for expiring verifications every hour for as long as the bot runs.
+pub fn schedule_verification_expiry(ctx: Context) {
+    if EXPIRY_SCHEDULED.swap(true, Ordering::SeqCst) {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(EXPIRY_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(err) = check_verification_expiry(&ctx).await {
+                error!("Error checking verification expiry: {}", err);
+            }
+        }
+    });
+}
+
+/// Records a verifier's decision in the mod log of the guild the review happened in.
+pub async fn log_verification(
+    ctx: &Context,
+    guild: Option<GuildId>,
+    reviewer: &User,
+    target: UserId,
+    action_type: ModActionType,
+    award: Option<String>,
+    reason: Option<String>,
+) {
+    let guild = match guild {
+        Some(g) => g,
+        None => return,
+    };
+
+    let action = ModAction {
+        target: Some(target),
+        moderator: reviewer.clone(),
+        action_type,
+        reason,
+        details: None,
+        guild,
+        case_id: None,
+        old_value: None,
+        new_value: award,
+    };
+    log_mod_action(action, ctx).await;
+}
+
+/// The reactions added to a queue message: one per active type, then close and the deny reasons.
+fn review_emojis(types: &[VerificationType]) -> Vec<String> {
+    let mut emojis: Vec<String> = types.iter().map(|t| t.emoji.clone()).collect();
+    emojis.extend(
+        [CLOSE_EMOJI, "⚠", "⛔"]
+            .iter()
+            .map(|e| e.to_string()),
+    );
+
+    emojis
+}
 
-    emoji_vec
+/// Whether the emoji is one of the deny reactions on the queue message.
+pub fn match_deny_emoji(emoji: &str) -> bool {
+    match_deny_reason(emoji).is_some()
 }
 
-fn match_verify_type(emoji_used: &str) -> Option<VerifyType> {
-    let emoji_vec = define_emoji_vec();
+fn match_deny_reason(emoji_used: &str) -> Option<DenyReason> {
     match emoji_used {
-        "🦅" => return Some(VerifyType::Eagle),
-        "⛰" => return Some(VerifyType::SummitSilver),
-        "🏕" => return Some(VerifyType::CampStaff),
-        "🛂" => return Some(VerifyType::Ypt),
-        "↗" => return Some(VerifyType::Ordeal),
-        "🟥" => return Some(VerifyType::Brotherhood),
-        "🔺" => return Some(VerifyType::Vigil),
-        "🚢" => return Some(VerifyType::Quartermaster),
-        "❌" => return Some(VerifyType::Close),
-        _ => return None,
+        "⛔" => Some(DenyReason::InsufficientProof),
+        "⚠" => Some(DenyReason::Illegible),
+        _ => None,
     }
 }